@@ -1,10 +1,20 @@
+use crate::export;
+use crate::expr;
+use crate::load;
+use crate::scopes;
+use crate::search;
+use crate::stats;
 use crate::vcd;
 use crate::wave;
+use crate::wavedrom;
 use eframe::egui;
 use eframe::egui::NumExt;
 use egui::*;
+use egui_dnd::DragDropItem;
 
-use std::sync::atomic::AtomicBool;
+use indexmap::IndexMap;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 
 use std::future::Future;
@@ -13,12 +23,44 @@ use std::task::Poll;
 #[derive(serde::Deserialize, serde::Serialize)]
 #[serde(default)]
 pub struct TemplateApp {
-    wave_data: Vec<(String, vcd::Signal)>,
+    wave_data: Vec<WaveRow>,
+    // every var in the open file, shown as a tree for adding them to `wave_data`
+    #[serde(skip)]
+    file_vars: Vec<vcd::ScopedVar>,
+    // the signal of each file var, when it's been read
+    #[serde(skip)]
+    file_signals: Vec<Option<Arc<vcd::Signal>>>,
+    #[serde(skip)]
+    scope_tree: scopes::ScopeTree,
     x_scale: Option<f32>,
     final_time: u64,
+    timescale: Option<vcd::Timescale>,
+    not_recorded: Vec<std::ops::Range<u64>>,
+    // show times as ticks rather than using the timescale
+    raw_ticks: bool,
+    // only index files when they're opened, reading signals when they're first shown
+    lazy_loading: bool,
+    // keep the newest time in view when following a file
+    follow_scroll: bool,
+    // the file last opened from disk, to reload
+    #[cfg(not(target_arch = "wasm32"))]
+    #[serde(skip)]
+    path: Option<std::path::PathBuf>,
+    // whether the file being loaded is the open one again, keeping the rows shown
+    #[serde(skip)]
+    reloading: bool,
     x_offset: Option<f32>,
     y_offset: f32,
     drag_time_start: Option<usize>,
+    // the times between the ends of the last drag
+    #[serde(skip)]
+    selection: Option<std::ops::RangeInclusive<u64>>,
+    // the time last clicked on, moved along the selected row by the navigation buttons
+    #[serde(skip)]
+    cursor: Option<u64>,
+    // the row the cursor moves along
+    #[serde(skip)]
+    selected: Option<egui::Id>,
     #[serde(skip)]
     dropped_files: Vec<egui::DroppedFile>,
     main_viewport: egui::Rect,
@@ -26,13 +68,41 @@ pub struct TemplateApp {
     #[serde(skip)]
     a_future: Option<std::pin::Pin<Box<dyn Future<Output = Option<OpenedVcd>>>>>,
     #[serde(skip)]
+    a_save: Option<SaveFuture>,
+    #[serde(skip)]
     open_file_ctx: Option<OpenFileCtx>,
     #[serde(skip)]
+    loading: Option<load::Loading>,
+    // the file signals are read from when they haven't been yet
+    #[serde(skip)]
+    index: Option<Arc<load::Index>>,
+    #[serde(skip)]
+    signal_loading: Option<load::Loading<load::Signals>>,
+    // a file still being written
+    #[serde(skip)]
+    following: Option<load::Following>,
+    #[serde(skip)]
     pub download: Arc<Mutex<Download>>,
     #[serde(skip)]
     url_window: UrlWindow,
     #[serde(skip)]
     err_window: ErrWindow,
+    #[serde(skip)]
+    warnings_window: WarningsWindow,
+    #[serde(skip)]
+    export_window: ExportWindow,
+    #[serde(skip)]
+    import_window: ImportWindow,
+    #[serde(skip)]
+    slice_window: SliceWindow,
+    #[serde(skip)]
+    concat_window: ConcatWindow,
+    #[serde(skip)]
+    expr_window: ExprWindow,
+    #[serde(skip)]
+    find_panel: FindPanel,
+    #[serde(skip)]
+    stats_panel: StatsPanel,
     row_height: f32,
     side_panel: SidePanel,
     info: Info,
@@ -43,18 +113,37 @@ impl Default for TemplateApp {
     fn default() -> Self {
         Self {
             wave_data: vec![],
+            file_vars: vec![],
+            file_signals: vec![],
+            scope_tree: scopes::ScopeTree::default(),
             x_scale: None,
             x_offset: None,
             y_offset: 0.0,
             final_time: 1,
+            timescale: None,
+            not_recorded: vec![],
+            raw_ticks: false,
+            lazy_loading: false,
+            follow_scroll: true,
+            #[cfg(not(target_arch = "wasm32"))]
+            path: None,
+            reloading: false,
             drag_time_start: None,
+            selection: None,
+            cursor: None,
+            selected: None,
             dropped_files: vec![],
             main_viewport: egui::Rect::from_min_size(
                 egui::pos2(0.0, 0.0),
                 egui::vec2(100.0, 800.0),
             ),
             a_future: None,
+            a_save: None,
             open_file_ctx: None,
+            loading: None,
+            index: None,
+            signal_loading: None,
+            following: None,
             download: Arc::new(Mutex::new(Download::None)),
             url_window: UrlWindow {
                 url: "".to_owned(),
@@ -64,6 +153,14 @@ impl Default for TemplateApp {
                 msg: String::new(),
                 open: false,
             },
+            warnings_window: WarningsWindow::default(),
+            export_window: ExportWindow::default(),
+            import_window: ImportWindow::default(),
+            slice_window: SliceWindow::default(),
+            concat_window: ConcatWindow::default(),
+            expr_window: ExprWindow::default(),
+            find_panel: FindPanel::default(),
+            stats_panel: StatsPanel::default(),
 
             row_height: 32.0,
 
@@ -85,9 +182,353 @@ impl Default for TemplateApp {
     }
 }
 
-#[derive(serde::Deserialize, serde::Serialize)]
+/// A signal shown in the wave view along with the variable information needed to label it.
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
+struct WaveRow {
+    name: String,
+    // where the signal was declared, kept so it can be written out again
+    var: vcd::ScopedVar,
+    // not read yet when the file is only indexed
+    signal: Option<Arc<vcd::Signal>>,
+    #[serde(default)]
+    interpolation: wave::Interpolation,
+    #[serde(default)]
+    radix: vcd::Radix,
+    #[serde(default)]
+    source: RowSource,
+    // how the signal is made from the signals of `parts` for rows that aren't in any file
+    #[serde(default)]
+    derived: Option<Derived>,
+    #[serde(default)]
+    parts: Vec<WaveRow>,
+    // the signals of `parts` when `signal` was last made from them
+    #[serde(skip)]
+    inputs: Vec<Arc<vcd::Signal>>,
+    // why the signal couldn't be made from them
+    #[serde(skip)]
+    error: Option<String>,
+    // tells rows apart for drag and drop, even ones with the same name and code
+    #[serde(skip, default = "next_row_id")]
+    id: u64,
+}
+
+/// A fresh id for a [`WaveRow`].
+fn next_row_id() -> u64 {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    NEXT.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Where the signal of a row comes from.
+#[derive(Clone, Copy, PartialEq, Eq, Default, serde::Deserialize, serde::Serialize)]
+enum RowSource {
+    /// The open waveform, signals are found by their id code.
+    #[default]
+    File,
+    /// Another file laid over the waveform, i.e. a WaveDrom diagram. Its codes mean nothing to the
+    /// open waveform.
+    Imported,
+}
+
+/// How the signal of a derived row is made from the signals of its parts.
+#[derive(Clone, PartialEq, Eq, Debug, serde::Deserialize, serde::Serialize)]
+enum Derived {
+    /// Some of the bits of the only part, counting from its most significant bit.
+    Slice(std::ops::Range<usize>),
+    /// The parts side by side, the first being the most significant.
+    Concat,
+    /// An [`expr::Expr`] of the parts, one for each name in it.
+    Expr(String),
+}
+
+impl WaveRow {
+    /// The id code of the signal in the open waveform.
+    fn file_code(&self) -> Option<::vcd::IdCode> {
+        (self.source == RowSource::File && self.derived.is_none()).then_some(self.var.var.code)
+    }
+
+    /// Call `f` with the row and every row it's derived from.
+    fn visit(&mut self, f: &mut impl FnMut(&mut WaveRow)) {
+        f(self);
+        for part in &mut self.parts {
+            part.visit(f);
+        }
+    }
+
+    /// The codes of the file signals the row is waiting on.
+    fn unread(&self, codes: &mut Vec<::vcd::IdCode>) {
+        match self.file_code() {
+            Some(code) if self.signal.is_none() => codes.push(code),
+            _ => self.parts.iter().for_each(|part| part.unread(codes)),
+        }
+    }
+
+    /// Make the signal of a derived row again if its parts' signals have changed since it was last
+    /// made. It's kept while any of them are missing.
+    fn derive(&mut self) {
+        let Some(derived) = &self.derived else {
+            return;
+        };
+        let mut inputs = vec![];
+        for part in &mut self.parts {
+            part.derive();
+            match &part.signal {
+                Some(signal) => inputs.push(Arc::clone(signal)),
+                None => return,
+            }
+        }
+        let made = self.signal.is_some() || self.error.is_some();
+        let same = inputs.len() == self.inputs.len()
+            && inputs
+                .iter()
+                .zip(&self.inputs)
+                .all(|(a, b)| Arc::ptr_eq(a, b));
+        if made && same {
+            return;
+        }
+        let signal = match derived {
+            Derived::Slice(bits) => inputs[0].slice(bits.clone()),
+            Derived::Concat => {
+                let parts: Vec<_> = inputs.iter().map(|s| &**s).collect();
+                vcd::Signal::concat(&parts)
+            }
+            Derived::Expr(text) => expr::Expr::parse(text).and_then(|expr| {
+                let signals: Vec<_> = expr
+                    .names()
+                    .into_iter()
+                    .zip(inputs.iter().map(|s| &**s))
+                    .collect();
+                expr.eval(&signals)
+            }),
+        };
+        match signal {
+            Ok(signal) => {
+                self.var.var.size = signal.width() as u32;
+                self.signal = Some(Arc::new(signal));
+                self.error = None;
+            }
+            Err(err) => {
+                self.signal = None;
+                self.error = Some(err.to_string());
+            }
+        }
+        self.inputs = inputs;
+    }
+
+    /// A row made from `parts` rather than read from a file.
+    fn new_derived(
+        name: String,
+        var: vcd::ScopedVar,
+        derived: Derived,
+        parts: Vec<WaveRow>,
+    ) -> WaveRow {
+        let mut row = WaveRow {
+            radix: parts.first().map_or_else(vcd::Radix::default, |p| p.radix),
+            name,
+            var,
+            signal: None,
+            interpolation: wave::Interpolation::default(),
+            source: RowSource::File,
+            derived: Some(derived),
+            parts,
+            inputs: vec![],
+            error: None,
+            id: next_row_id(),
+        };
+        row.derive();
+        row
+    }
+
+    /// A row of the bits `msb` down to `lsb` of this one, numbered as they're declared.
+    fn slice(&self, msb: i32, lsb: i32) -> WaveRow {
+        let (first, _) = declared_bits(&self.var.var);
+        let pos = |bit: i32| bit.abs_diff(first) as usize;
+        let (msb, lsb) = if pos(msb) <= pos(lsb) {
+            (msb, lsb)
+        } else {
+            (lsb, msb)
+        };
+        let bits = pos(msb)..pos(lsb) + 1;
+        // a slice of a slice is named after the bus
+        let name = match self.derived {
+            Some(Derived::Slice(_)) => self.name.rsplit_once('[').map_or(&*self.name, |(n, _)| n),
+            _ => &self.name,
+        };
+        let mut var = self.var.clone();
+        var.var.size = bits.len() as u32;
+        let (index, name) = if msb == lsb {
+            (
+                ::vcd::ReferenceIndex::BitSelect(msb),
+                format!("{name}[{msb}]"),
+            )
+        } else {
+            (
+                ::vcd::ReferenceIndex::Range(msb, lsb),
+                format!("{name}[{msb}:{lsb}]"),
+            )
+        };
+        var.var.index = Some(index);
+        WaveRow::new_derived(name, var, Derived::Slice(bits), vec![self.clone()])
+    }
+
+    /// A bus of `parts` side by side, the first being the most significant.
+    fn concat(name: String, parts: Vec<WaveRow>) -> WaveRow {
+        let var = vcd::ScopedVar {
+            scopes: parts
+                .first()
+                .map_or_else(Vec::new, |p| p.var.scopes.clone()),
+            var: vcd::Var {
+                var_type: vcd::VarType::Wire,
+                size: parts.iter().map(|p| p.var.var.size).sum(),
+                code: ::vcd::IdCode::from(0u32),
+                reference: name.clone(),
+                index: None,
+            },
+        };
+        WaveRow::new_derived(name, var, Derived::Concat, parts)
+    }
+
+    /// The name of the bus a one bit row is part of and its bit number, for vars declared like
+    /// `data [3]` or `data[3]`.
+    fn bus_bit(&self) -> Option<(&str, i32)> {
+        let var = &self.var.var;
+        if var.size != 1 || var.var_type.is_real() || var.var_type == vcd::VarType::String {
+            return None;
+        }
+        if let Some(::vcd::ReferenceIndex::BitSelect(bit)) = var.index {
+            return Some((&self.name, bit));
+        }
+        let (bus, bit) = self.name.strip_suffix(']')?.rsplit_once('[')?;
+        Some((bus, bit.parse().ok()?))
+    }
+}
+
+/// The one bit rows named like `data[0]`, `data[1]`... that could be shown as a bus, for every bus
+/// whose bits are all there. Gives the bus names and their rows, most significant first.
+fn bus_groups(rows: &[WaveRow]) -> Vec<(&str, Vec<usize>)> {
+    let mut buses: IndexMap<&str, Vec<(i32, usize)>> = IndexMap::new();
+    for (i, row) in rows.iter().enumerate() {
+        if let Some((bus, bit)) = row.bus_bit() {
+            buses.entry(bus).or_default().push((bit, i));
+        }
+    }
+    buses
+        .into_iter()
+        .filter_map(|(bus, mut bits)| {
+            bits.sort_by_key(|&(bit, _)| std::cmp::Reverse(bit));
+            // bits missing or repeated, i.e. the same bus from different files
+            let whole = bits.windows(2).all(|w| w[0].0 == w[1].0 + 1);
+            (bits.len() > 1 && whole).then(|| (bus, bits.into_iter().map(|(_, i)| i).collect()))
+        })
+        .collect()
+}
+
+/// Replace the rows of each group from [`bus_groups`] with a bus made of them, where its first row
+/// was.
+fn group_bus_bits(rows: &mut Vec<WaveRow>) {
+    let mut buses = HashMap::new();
+    let mut grouped = vec![false; rows.len()];
+    for (bus, bits) in bus_groups(rows) {
+        let (msb, lsb) = (
+            rows[bits[0]].bus_bit(),
+            rows[bits[bits.len() - 1]].bus_bit(),
+        );
+        let (msb, lsb) = (msb.unwrap().1, lsb.unwrap().1);
+        let parts = bits.iter().map(|&i| rows[i].clone()).collect();
+        let mut row = WaveRow::concat(format!("{bus}[{msb}:{lsb}]"), parts);
+        let reference = &rows[bits[0]].var.var.reference;
+        row.var.var.reference = reference.split('[').next().unwrap_or(reference).to_owned();
+        row.var.var.index = Some(::vcd::ReferenceIndex::Range(msb, lsb));
+        for &i in &bits {
+            grouped[i] = true;
+        }
+        buses.insert(*bits.iter().min().unwrap(), row);
+    }
+    let old = std::mem::take(rows);
+    for (i, row) in old.into_iter().enumerate() {
+        rows.extend(buses.remove(&i));
+        if !grouped[i] {
+            rows.push(row);
+        }
+    }
+}
+
+/// The declared numbers of the most and least significant bits of a var, `[size-1:0]` when it
+/// doesn't say.
+fn declared_bits(var: &vcd::Var) -> (i32, i32) {
+    match var.index {
+        Some(::vcd::ReferenceIndex::Range(msb, lsb)) => (msb, lsb),
+        _ => (var.size as i32 - 1, 0),
+    }
+}
+
+/// What the cursor can be moved to along the selected row.
+#[derive(Clone, Copy, PartialEq)]
+enum Jump {
+    Change,
+    Rising,
+    Falling,
+}
+
+impl Jump {
+    const ALL: [Jump; 3] = [Jump::Change, Jump::Rising, Jump::Falling];
+
+    fn name(self) -> &'static str {
+        match self {
+            Jump::Change => "change",
+            Jump::Rising => "rise",
+            Jump::Falling => "fall",
+        }
+    }
+
+    /// The arrow key moving to the next or previous one.
+    fn shortcut(self, forward: bool) -> KeyboardShortcut {
+        let modifiers = match self {
+            Jump::Change => Modifiers::NONE,
+            Jump::Rising => Modifiers::SHIFT,
+            Jump::Falling => Modifiers::ALT,
+        };
+        let key = if forward {
+            Key::ArrowRight
+        } else {
+            Key::ArrowLeft
+        };
+        KeyboardShortcut::new(modifiers, key)
+    }
+
+    /// Whether there can be one on `signal`, edges are only found on one bit signals.
+    fn applies(self, signal: &vcd::Signal) -> bool {
+        self == Jump::Change || (signal.width() == 1 && !signal.is_real() && !signal.is_string())
+    }
+
+    /// The time of the next one after `time`, or the previous one before it.
+    fn find(self, signal: &vcd::Signal, time: u64, forward: bool) -> Option<u64> {
+        match (self, forward) {
+            (Jump::Change, true) => signal.next_change(time),
+            (Jump::Change, false) => signal.prev_change(time),
+            (Jump::Rising, true) => signal.next_edge(time, vcd::Value::V1),
+            (Jump::Rising, false) => signal.prev_edge(time, vcd::Value::V1),
+            (Jump::Falling, true) => signal.next_edge(time, vcd::Value::V0),
+            (Jump::Falling, false) => signal.prev_edge(time, vcd::Value::V0),
+        }
+    }
+}
+
+// the hash is used as the drag and drop id
+impl std::hash::Hash for WaveRow {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+#[derive(PartialEq, serde::Deserialize, serde::Serialize)]
 enum SidePanel {
     None,
+    /// The scopes of the open file.
+    Scopes,
+    /// Searching the selected row for a value.
+    Find,
+    /// Statistics of the selected row.
+    Stats,
     Info,
     Samples,
 }
@@ -149,51 +590,60 @@ impl Info {
 }
 
 impl TemplateApp {
-    pub fn new(
-        cc: &eframe::CreationContext<'_>,
-        sigs: Vec<(vcd::ScopedVar, vcd::Signal)>,
-        final_time: u64,
-    ) -> TemplateApp {
+    pub fn new(cc: &eframe::CreationContext<'_>) -> TemplateApp {
         if let Some(storage) = cc.storage {
             if let Some(app) = eframe::get_value(storage, eframe::APP_KEY) {
                 return app;
             }
         }
 
-        let wave_data = sigs
-            .into_iter()
-            .map(|(var, sig)| {
-                let mut name: String =
-                    itertools::intersperse(var.scopes.iter().map(|x| x.1.as_str()), ".").collect();
-                if !name.is_empty() {
-                    name.push('.');
-                }
-                name.push_str(&var.var.reference);
-                // let bools = sig.scalars().map(|(_, v)| v == vcd::Value::V1).collect();
-                // eprintln!("bools = {bools:?}");
-                (name, sig)
-            })
-            .collect();
         Self {
-            wave_data,
-            final_time,
+            wave_data: vec![],
+            file_vars: vec![],
+            file_signals: vec![],
+            scope_tree: scopes::ScopeTree::default(),
+            final_time: 1,
+            timescale: None,
+            not_recorded: vec![],
+            raw_ticks: false,
+            lazy_loading: false,
+            follow_scroll: true,
+            #[cfg(not(target_arch = "wasm32"))]
+            path: None,
+            reloading: false,
             x_scale: None, // 3.0,
             x_offset: None,
             y_offset: 0.0,
             drag_time_start: None,
+            selection: None,
+            cursor: None,
+            selected: None,
             dropped_files: vec![],
             main_viewport: egui::Rect::from_min_size(
                 egui::pos2(0.0, 0.0),
                 egui::vec2(100.0, 800.0),
             ),
             a_future: None,
+            a_save: None,
             open_file_ctx: None,
+            loading: None,
+            index: None,
+            signal_loading: None,
+            following: None,
             download: Arc::new(Mutex::new(Download::None)),
             url_window: UrlWindow {
                 url: "https://raw.githubusercontent.com/Mohammad-Heydariii/Digital-Systems-Lab-Course/main/Lab_project4/modelsim_files/clkdiv2n_tb.vcd".to_owned(),
                 open: false,
             },
             err_window: ErrWindow { msg: String::new(), open: false },
+            warnings_window: WarningsWindow::default(),
+            export_window: ExportWindow::default(),
+            import_window: ImportWindow::default(),
+            slice_window: SliceWindow::default(),
+            concat_window: ConcatWindow::default(),
+            expr_window: ExprWindow::default(),
+            find_panel: FindPanel::default(),
+            stats_panel: StatsPanel::default(),
 
             row_height: 32.0,
 
@@ -205,6 +655,298 @@ impl TemplateApp {
     }
 }
 
+impl TemplateApp {
+    /// Start loading a waveform file in the background.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_path(&mut self, path: std::path::PathBuf) {
+        self.start_loading(load::Loading::from_path(path.clone(), self.lazy_loading));
+        self.path = Some(path);
+    }
+
+    /// Follow a VCD file that's still being written, showing value changes as they're appended.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn follow_path(&mut self, path: std::path::PathBuf) {
+        if let Some(old) = self.loading.take() {
+            old.cancel();
+        }
+        self.following = Some(load::Following::from_path(path));
+        self.path = None;
+    }
+
+    fn start_loading(&mut self, loading: load::Loading) {
+        if let Some(old) = self.loading.replace(loading) {
+            old.cancel();
+        }
+        self.reloading = false;
+    }
+
+    /// Replace the current waveform with a newly opened one.
+    fn set_waveform(&mut self, opened: load::Opened) {
+        if let Some(old) = self.signal_loading.take() {
+            old.cancel();
+        }
+        match opened {
+            load::Opened::Waveform(waveform) => {
+                (self.file_vars, self.file_signals) = waveform
+                    .vars
+                    .into_iter()
+                    .map(|(var, signal)| (var, Some(signal)))
+                    .unzip();
+                self.final_time = waveform.final_time;
+                self.timescale = waveform.timescale;
+                self.not_recorded = waveform.not_recorded;
+                self.index = None;
+            }
+            load::Opened::Index(index) => {
+                self.file_vars = index.vars().to_vec();
+                self.file_signals = vec![None; self.file_vars.len()];
+                self.final_time = index.final_time();
+                self.timescale = index.timescale();
+                self.not_recorded = index.not_recorded().to_vec();
+                self.index = Some(Arc::new(index));
+            }
+        }
+        self.scope_tree = scopes::ScopeTree::new(&self.file_vars);
+        if std::mem::take(&mut self.reloading) {
+            self.keep_rows();
+            return;
+        }
+        // signals are picked from the scopes rather than all shown
+        self.wave_data = vec![];
+        self.side_panel = SidePanel::Scopes;
+        self.x_scale = None;
+        self.x_offset = None;
+        self.selection = None;
+        self.cursor = None;
+        self.selected = None;
+        self.find_panel.results = None;
+    }
+
+    /// Point the rows at the vars of a reloaded file. They're found by name as their codes can
+    /// change, rows whose vars have gone are dropped.
+    fn keep_rows(&mut self) {
+        type Key<'a> = (&'a [(vcd::ScopeType, String)], &'a str, Option<String>);
+        fn key(var: &vcd::ScopedVar) -> Key<'_> {
+            let index = var.var.index.map(|index| index.to_string());
+            (&var.scopes, &var.var.reference, index)
+        }
+        let found: HashMap<Key<'_>, usize> = self
+            .file_vars
+            .iter()
+            .enumerate()
+            .map(|(i, var)| (key(var), i))
+            .collect();
+        let (file_vars, file_signals) = (&self.file_vars, &self.file_signals);
+        self.wave_data.retain_mut(|row| {
+            let mut kept = true;
+            row.visit(&mut |row| {
+                if row.file_code().is_some() {
+                    match found.get(&key(&row.var)) {
+                        Some(&i) => {
+                            row.var.var.code = file_vars[i].var.code;
+                            row.signal = file_signals[i].clone();
+                        }
+                        None => kept = false,
+                    }
+                } else if row.derived.is_some() {
+                    row.signal = None;
+                    row.error = None;
+                    row.inputs.clear();
+                }
+            });
+            kept
+        });
+    }
+
+    /// Start reading the signals for `codes` from the index, unless some are already being read.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn read_signals(&mut self, codes: Vec<::vcd::IdCode>) {
+        if codes.is_empty() || self.signal_loading.is_some() {
+            return;
+        }
+        if let Some(index) = &self.index {
+            self.signal_loading = Some(load::Loading::read_signals(index.clone(), codes));
+        }
+    }
+
+    // the web build never indexes files
+    #[cfg(target_arch = "wasm32")]
+    fn read_signals(&mut self, _codes: Vec<::vcd::IdCode>) {}
+
+    /// Give the rows their signals once they've been read.
+    fn finish_signal_loading(&mut self, ctx: &egui::Context) {
+        let Some(loading) = &mut self.signal_loading else {
+            return;
+        };
+        let Some(result) = loading.try_finish() else {
+            ctx.request_repaint_after(std::time::Duration::from_millis(100));
+            return;
+        };
+        self.signal_loading = None;
+        match result {
+            Ok(signals) => {
+                if !signals.warnings.is_empty() {
+                    self.warnings_window.open = true;
+                    self.warnings_window.warnings.extend(signals.warnings);
+                }
+                for (code, signal) in signals.signals {
+                    let signal = Arc::new(signal);
+                    // kept for when more of the file's vars are added
+                    for (var, file_signal) in self.file_vars.iter().zip(&mut self.file_signals) {
+                        if var.var.code == code {
+                            *file_signal = Some(Arc::clone(&signal));
+                        }
+                    }
+                    // aliases share the signal
+                    for row in &mut self.wave_data {
+                        row.visit(&mut |row| {
+                            if row.file_code() == Some(code) {
+                                row.signal = Some(Arc::clone(&signal));
+                            }
+                        });
+                    }
+                }
+            }
+            Err(err) => {
+                // stop trying to read from a file that's gone bad
+                self.index = None;
+                self.err_window.msg = format!("failed to read signals:\n{err}");
+                self.err_window.open = true;
+            }
+        }
+    }
+
+    /// Swap in the signals of a followed file if its thread has read more of it.
+    fn update_following(&mut self, ctx: &egui::Context) {
+        let Some(following) = &mut self.following else {
+            return;
+        };
+        // the file is read on another thread so keep checking
+        ctx.request_repaint_after(std::time::Duration::from_millis(200));
+        let started = following.started();
+        let waveform = match following.receive() {
+            Ok(Some(waveform)) => waveform,
+            Ok(None) => return,
+            Err(err) => {
+                self.err_window.msg = format!("{} failed to follow:\n{err}", following.name);
+                self.err_window.open = true;
+                self.following = None;
+                return;
+            }
+        };
+        if waveform.warnings.len() != self.warnings_window.warnings.len() {
+            self.warnings_window = WarningsWindow {
+                open: self.warnings_window.open || self.warnings_window.warnings.is_empty(),
+                name: following.name.clone(),
+                warnings: waveform.warnings.clone(),
+            };
+        }
+        if !started {
+            self.set_waveform(load::Opened::Waveform(waveform));
+            // nothing may have been simulated yet
+            self.final_time = self.final_time.max(1);
+            return;
+        }
+        // the vars are the same each time, only the signals have grown
+        for (file_signal, (_, signal)) in self.file_signals.iter_mut().zip(&waveform.vars) {
+            *file_signal = Some(Arc::clone(signal));
+        }
+        let signals: HashMap<_, _> = waveform
+            .vars
+            .iter()
+            .map(|(var, signal)| (var.var.code, signal))
+            .collect();
+        for row in &mut self.wave_data {
+            row.visit(&mut |row| {
+                if let Some(code) = row.file_code() {
+                    row.signal = signals.get(&code).map(|&signal| Arc::clone(signal));
+                }
+            });
+        }
+        self.final_time = waveform.final_time.max(1);
+        self.not_recorded = waveform.not_recorded;
+        if self.follow_scroll {
+            if let Some(x_scale) = self.x_scale {
+                let width = self.final_time as f32 * x_scale * 32.0;
+                self.x_offset = Some((width - self.main_viewport.width()).max(0.0));
+            }
+        }
+    }
+
+    /// Swap in the waveform being loaded if it's done, otherwise show the progress.
+    fn show_loading(&mut self, ctx: &egui::Context) {
+        let Some(loading) = &mut self.loading else {
+            return;
+        };
+        if let Some(result) = loading.try_finish() {
+            let name = loading.name.clone();
+            self.loading = None;
+            match result {
+                Ok(opened) => {
+                    self.following = None;
+                    let warnings = match &opened {
+                        load::Opened::Waveform(waveform) => waveform.warnings.clone(),
+                        load::Opened::Index(index) => index.warnings().to_vec(),
+                    };
+                    self.warnings_window = WarningsWindow {
+                        open: !warnings.is_empty(),
+                        name,
+                        warnings,
+                    };
+                    self.set_waveform(opened);
+                }
+                Err(err) => {
+                    self.err_window.msg = match vcd::ParseError::from_io(&err) {
+                        Some(err) => {
+                            let mut msg = format!(
+                                "{name} failed to load:\n{}\n\nline {}, byte {}",
+                                err.msg, err.line, err.offset
+                            );
+                            if let Some(token) = &err.token {
+                                msg.push_str(&format!("\nat {token:?}"));
+                            }
+                            msg
+                        }
+                        None => format!("{name} failed to load:\n{err}"),
+                    };
+                    self.err_window.open = true;
+                }
+            }
+            return;
+        }
+
+        let read = loading.bytes_read();
+        let mut cancel = false;
+        egui::Window::new("Loading")
+            .id(egui::Id::new("loading_window"))
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.label(&loading.name);
+                let mb = read as f32 / 1e6;
+                let bar = match loading.total {
+                    Some(total) if total > 0 => {
+                        let frac = (read as f32 / total as f32).min(1.0);
+                        egui::ProgressBar::new(frac)
+                            .text(format!("{mb:.1} / {:.1} MB", total as f32 / 1e6))
+                    }
+                    _ => egui::ProgressBar::new(0.0).text(format!("{mb:.1} MB")),
+                };
+                ui.add(bar.desired_width(300.0));
+                cancel = ui.button("Cancel").clicked();
+            });
+        if cancel {
+            if let Some(loading) = self.loading.take() {
+                loading.cancel();
+            }
+        } else {
+            // the progress comes from another thread so keep polling
+            ctx.request_repaint_after(std::time::Duration::from_millis(100));
+        }
+    }
+}
+
 pub enum Download {
     None,
     InProgress,
@@ -240,12 +982,28 @@ pub enum Download {
 const RAW_WAKER_VTABLE: std::task::RawWakerVTable =
     std::task::RawWakerVTable::new(my_clone, my_wake_by_ref, my_wake_by_ref, my_drop);
 
-struct OpenedVcd {
-    // filename: String,
-    wave_data: Vec<(String, vcd::Signal)>,
-    time: u64,
+enum OpenedVcd {
+    // native files are read from disk in the background so they can be indexed
+    #[cfg(not(target_arch = "wasm32"))]
+    Path(std::path::PathBuf),
+    // a VCD file to keep reading as it's written
+    #[cfg(not(target_arch = "wasm32"))]
+    Follow(std::path::PathBuf),
+    #[cfg(target_arch = "wasm32")]
+    Bytes {
+        filename: String,
+        bytes: Vec<u8>,
+    },
+    // a diagram to lay over the waveform
+    WaveDrom {
+        filename: String,
+        bytes: Vec<u8>,
+    },
 }
 
+// saving goes through a dialog, which is async on the web
+type SaveFuture = std::pin::Pin<Box<dyn Future<Output = Option<std::io::Result<()>>>>>;
+
 struct OpenFileCtx {
     awoken: Arc<AtomicBool>,
     egui_ctx: egui::Context,
@@ -289,6 +1047,769 @@ impl ErrWindow {
     }
 }
 
+/// The problems skipped over while loading a file.
+#[derive(Default)]
+struct WarningsWindow {
+    name: String,
+    warnings: Vec<vcd::ParseError>,
+    open: bool,
+}
+
+impl WarningsWindow {
+    fn show(&mut self, ctx: &egui::Context) {
+        let window = egui::Window::new("Warnings")
+            .id(egui::Id::new("warnings_window"))
+            .resizable(true)
+            .collapsible(true)
+            .open(&mut self.open);
+        window.show(ctx, |ui| {
+            ui.label(format!(
+                "{} loaded with {} problems skipped:",
+                self.name,
+                self.warnings.len()
+            ));
+            egui::ScrollArea::vertical()
+                .max_height(300.0)
+                .show(ui, |ui| {
+                    for warning in &self.warnings {
+                        ui.monospace(warning.to_string());
+                    }
+                });
+        });
+    }
+}
+
+/// Choosing which signals and times to write to a file.
+#[derive(Default)]
+struct ExportWindow {
+    open: bool,
+    // whether each row of the wave view is written
+    chosen: Vec<bool>,
+    start: u64,
+    end: u64,
+    // the row of the clock CSV rows are sampled on, one row per change when there isn't one
+    sampled: bool,
+    clock: Option<usize>,
+    edge: export::Edge,
+}
+
+impl ExportWindow {
+    fn open(&mut self, chosen: Vec<bool>, range: std::ops::RangeInclusive<u64>) {
+        self.open = true;
+        self.chosen = chosen;
+        self.start = *range.start();
+        self.end = *range.end();
+    }
+
+    /// Show the window, giving the chosen rows and times once it's saved. Chosen rows whose
+    /// signals haven't been read yet are added to `unread`.
+    fn show(
+        &mut self,
+        ctx: &egui::Context,
+        rows: &[WaveRow],
+        final_time: u64,
+        unread: &mut Vec<::vcd::IdCode>,
+    ) -> Option<(ExportFormat, Vec<usize>, std::ops::RangeInclusive<u64>)> {
+        // the rows changed under us
+        if self.chosen.len() != rows.len() {
+            self.chosen = vec![true; rows.len()];
+            self.clock = None;
+        }
+        let mut save = None;
+        let mut open = self.open;
+        egui::Window::new("Export")
+            .id(egui::Id::new("export_window"))
+            .resizable(true)
+            .collapsible(false)
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("From");
+                    ui.add(egui::DragValue::new(&mut self.start).range(0..=self.end));
+                    ui.label("to");
+                    ui.add(egui::DragValue::new(&mut self.end).range(self.start..=final_time));
+                });
+                ui.horizontal(|ui| {
+                    if ui.button("All").clicked() {
+                        self.chosen.fill(true);
+                    }
+                    if ui.button("None").clicked() {
+                        self.chosen.fill(false);
+                    }
+                });
+                egui::ScrollArea::vertical()
+                    .max_height(300.0)
+                    .show(ui, |ui| {
+                        for (chosen, row) in self.chosen.iter_mut().zip(rows) {
+                            ui.checkbox(chosen, &row.name);
+                        }
+                    });
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.label("CSV rows");
+                    ui.radio_value(&mut self.sampled, false, "Every change");
+                    ui.radio_value(&mut self.sampled, true, "Clock edges");
+                });
+                if self.sampled {
+                    ui.horizontal(|ui| {
+                        let name = self.clock.map_or("", |i| &rows[i].name);
+                        egui::ComboBox::from_id_source("export_clock")
+                            .selected_text(name)
+                            .show_ui(ui, |ui| {
+                                for (i, row) in rows.iter().enumerate() {
+                                    let var = &row.var.var;
+                                    let scalar = var.size == 1
+                                        && !var.var_type.is_real()
+                                        && var.var_type != vcd::VarType::String;
+                                    if scalar {
+                                        ui.selectable_value(&mut self.clock, Some(i), &row.name);
+                                    }
+                                }
+                            });
+                        egui::ComboBox::from_id_source("export_edge")
+                            .selected_text(self.edge.name())
+                            .show_ui(ui, |ui| {
+                                for edge in export::Edge::ALL {
+                                    ui.selectable_value(&mut self.edge, edge, edge.name());
+                                }
+                            });
+                    });
+                }
+                let clock = self.clock.filter(|_| self.sampled);
+                let mut ready = true;
+                for (i, (&chosen, row)) in self.chosen.iter().zip(rows).enumerate() {
+                    if (chosen || clock == Some(i)) && row.signal.is_none() {
+                        row.unread(unread);
+                        ready = false;
+                    }
+                }
+                ui.horizontal(|ui| {
+                    let enabled = ready && self.chosen.contains(&true);
+                    if ui
+                        .add_enabled(enabled, egui::Button::new("Save VCD…"))
+                        .clicked()
+                    {
+                        save = Some(ExportFormat::Vcd);
+                    }
+                    if ui
+                        .add_enabled(enabled, egui::Button::new("Save WaveDrom…"))
+                        .on_hover_text("WaveJSON with clocks and bus values, for docs")
+                        .clicked()
+                    {
+                        save = Some(ExportFormat::WaveDrom);
+                    }
+                    if ui
+                        .add_enabled(
+                            enabled && (!self.sampled || clock.is_some()),
+                            egui::Button::new("Save CSV…"),
+                        )
+                        .on_hover_text("Values written with each signal's radix")
+                        .clicked()
+                    {
+                        save = Some(ExportFormat::Csv {
+                            clock: clock.map(|i| (i, self.edge)),
+                        });
+                    }
+                    if !ready {
+                        ui.spinner();
+                        ui.label("reading signals…");
+                    }
+                });
+            });
+        self.open = open && save.is_none();
+        let chosen = self.chosen.iter().enumerate().filter(|(_, &c)| c);
+        save.map(|format| {
+            let rows = chosen.map(|(i, _)| i).collect();
+            (format, rows, self.start..=self.end)
+        })
+    }
+}
+
+#[derive(Clone, Copy)]
+enum ExportFormat {
+    Vcd,
+    WaveDrom,
+    /// Sampled on the edges of the clock row when there is one.
+    Csv {
+        clock: Option<(usize, export::Edge)>,
+    },
+}
+
+/// Where to put the cells of a WaveDrom diagram being imported.
+#[derive(Default)]
+struct ImportWindow {
+    open: bool,
+    name: String,
+    text: String,
+    start: u64,
+    // ticks per cell
+    tick: u64,
+}
+
+impl ImportWindow {
+    /// Show the window, giving the diagram's signals once it's imported.
+    fn show(&mut self, ctx: &egui::Context) -> Option<std::io::Result<vcd::Waveform>> {
+        let mut import = false;
+        let mut open = self.open;
+        egui::Window::new("Import WaveDrom")
+            .id(egui::Id::new("import_window"))
+            .collapsible(false)
+            .resizable(false)
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.label(&self.name);
+                egui::Grid::new("import_grid").show(ui, |ui| {
+                    ui.label("Start");
+                    ui.add(egui::DragValue::new(&mut self.start));
+                    ui.end_row();
+                    ui.label("Ticks per cell");
+                    ui.add(egui::DragValue::new(&mut self.tick).range(1..=u64::MAX));
+                    ui.end_row();
+                });
+                import = ui.button("Import").clicked();
+            });
+        self.open = open && !import;
+        import.then(|| crate::wavedrom::read_wavejson(&self.text, self.start, self.tick))
+    }
+}
+
+/// Picking the bits of a bus to show in a row of their own.
+#[derive(Default)]
+struct SliceWindow {
+    open: bool,
+    // the row being sliced
+    name: String,
+    // its most and least significant bits as they're declared
+    first: i32,
+    last: i32,
+    msb: i32,
+    lsb: i32,
+}
+
+impl SliceWindow {
+    fn open(&mut self, row: &WaveRow) {
+        let (first, last) = declared_bits(&row.var.var);
+        *self = SliceWindow {
+            open: true,
+            name: row.name.clone(),
+            first,
+            last,
+            msb: first,
+            lsb: last,
+        };
+    }
+
+    /// Show the window, giving the row's name and the bits to slice from it once they're picked.
+    fn show(&mut self, ctx: &egui::Context) -> Option<(&str, i32, i32)> {
+        let mut add = false;
+        let mut open = self.open;
+        let bits = self.first.min(self.last)..=self.first.max(self.last);
+        egui::Window::new("Slice")
+            .id(egui::Id::new("slice_window"))
+            .collapsible(false)
+            .resizable(false)
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.label(&self.name);
+                egui::Grid::new("slice_grid").show(ui, |ui| {
+                    ui.label("MSB");
+                    ui.add(egui::DragValue::new(&mut self.msb).range(bits.clone()));
+                    ui.end_row();
+                    ui.label("LSB");
+                    ui.add(egui::DragValue::new(&mut self.lsb).range(bits));
+                    ui.end_row();
+                });
+                add = ui.button("Add").clicked();
+            });
+        self.open = open && !add;
+        add.then_some((&*self.name, self.msb, self.lsb))
+    }
+}
+
+/// Picking rows to put side by side as a bus.
+#[derive(Default)]
+struct ConcatWindow {
+    open: bool,
+    name: String,
+    // the rows in the bus, in the order they were picked
+    picked: Vec<usize>,
+    // whether the first row picked is the most significant
+    msb_first: bool,
+}
+
+impl ConcatWindow {
+    fn open(&mut self) {
+        *self = ConcatWindow {
+            open: true,
+            name: "bus".to_owned(),
+            picked: vec![],
+            msb_first: true,
+        };
+    }
+
+    /// Show the window, giving the bus's name and its rows, most significant first, once it's made.
+    fn show(&mut self, ctx: &egui::Context, rows: &[WaveRow]) -> Option<(String, Vec<usize>)> {
+        // the rows changed under us
+        self.picked.retain(|&i| i < rows.len());
+        let mut add = false;
+        let mut open = self.open;
+        egui::Window::new("Concatenate")
+            .id(egui::Id::new("concat_window"))
+            .collapsible(false)
+            .resizable(true)
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Name");
+                    ui.text_edit_singleline(&mut self.name);
+                });
+                ui.horizontal(|ui| {
+                    ui.radio_value(&mut self.msb_first, true, "First picked is MSB");
+                    ui.radio_value(&mut self.msb_first, false, "First picked is LSB");
+                });
+                egui::ScrollArea::vertical()
+                    .max_height(300.0)
+                    .show(ui, |ui| {
+                        for (i, row) in rows.iter().enumerate() {
+                            let var = &row.var.var;
+                            if var.var_type.is_real() || var.var_type == vcd::VarType::String {
+                                continue;
+                            }
+                            let place = self.picked.iter().position(|&p| p == i);
+                            let mut picked = place.is_some();
+                            let text = match place {
+                                Some(place) => format!("{} ({})", row.name, place + 1),
+                                None => row.name.clone(),
+                            };
+                            if ui.checkbox(&mut picked, text).changed() {
+                                if picked {
+                                    self.picked.push(i);
+                                } else {
+                                    self.picked.retain(|&p| p != i);
+                                }
+                            }
+                        }
+                    });
+                let width: u32 = self.picked.iter().map(|&i| rows[i].var.var.size).sum();
+                ui.horizontal(|ui| {
+                    let enabled = self.picked.len() > 1 && !self.name.is_empty();
+                    add = ui.add_enabled(enabled, egui::Button::new("Add")).clicked();
+                    ui.label(format!("{width} bits"));
+                });
+            });
+        self.open = open && !add;
+        add.then(|| {
+            let mut picked = std::mem::take(&mut self.picked);
+            if !self.msb_first {
+                picked.reverse();
+            }
+            (self.name.clone(), picked)
+        })
+    }
+}
+
+/// Writing an expression for a row made from other rows.
+#[derive(Default)]
+struct ExprWindow {
+    open: bool,
+    name: String,
+    text: String,
+    error: Option<String>,
+}
+
+impl ExprWindow {
+    fn open(&mut self) {
+        self.open = true;
+        self.error = None;
+    }
+
+    /// Show the window, giving the new row once its expression checks out.
+    fn show(&mut self, ctx: &egui::Context, rows: &[WaveRow]) -> Option<WaveRow> {
+        let mut add = false;
+        let mut open = self.open;
+        egui::Window::new("Expression")
+            .id(egui::Id::new("expr_window"))
+            .collapsible(false)
+            .resizable(false)
+            .open(&mut open)
+            .show(ctx, |ui| {
+                egui::Grid::new("expr_grid").show(ui, |ui| {
+                    ui.label("Name");
+                    ui.text_edit_singleline(&mut self.name);
+                    ui.end_row();
+                    ui.label("Expression");
+                    let resp = ui.add(
+                        egui::TextEdit::singleline(&mut self.text)
+                            .code_editor()
+                            .hint_text("valid && ready"),
+                    );
+                    add = resp.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                    ui.end_row();
+                });
+                ui.label(
+                    RichText::new("e.g. count == 8'hff, addr[15:0] + 4, {a, b}, sel ? a : b")
+                        .small()
+                        .weak(),
+                );
+                if let Some(err) = &self.error {
+                    ui.colored_label(ui.visuals().error_fg_color, err);
+                }
+                add |= ui.button("Add").clicked();
+            });
+        self.open = open;
+        if !add {
+            return None;
+        }
+        match self.row(rows) {
+            Ok(row) => {
+                self.open = false;
+                self.error = None;
+                Some(row)
+            }
+            Err(err) => {
+                self.error = Some(err);
+                None
+            }
+        }
+    }
+
+    fn row(&self, rows: &[WaveRow]) -> Result<WaveRow, String> {
+        let expr = expr::Expr::parse(&self.text).map_err(|err| err.to_string())?;
+        let parts = expr
+            .names()
+            .into_iter()
+            .map(|name| find_row(rows, name).cloned())
+            .collect::<Result<_, _>>()?;
+        let name = match self.name.trim() {
+            "" => self.text.trim().to_owned(),
+            name => name.to_owned(),
+        };
+        let var = vcd::ScopedVar {
+            scopes: vec![],
+            var: vcd::Var {
+                var_type: vcd::VarType::Logic,
+                size: 1,
+                code: ::vcd::IdCode::from(0u32),
+                reference: name.clone(),
+                index: None,
+            },
+        };
+        let row = WaveRow::new_derived(name, var, Derived::Expr(self.text.clone()), parts);
+        // signals that haven't been read yet are checked once they are
+        match &row.error {
+            Some(err) => Err(err.clone()),
+            None => Ok(row),
+        }
+    }
+}
+
+/// The row a name in an expression means, the one with that name or else the only one whose name
+/// ends with it after a dot.
+fn find_row<'a>(rows: &'a [WaveRow], name: &str) -> Result<&'a WaveRow, String> {
+    if let Some(row) = rows.iter().find(|row| row.name == name) {
+        return Ok(row);
+    }
+    let suffix = format!(".{name}");
+    let mut found = rows.iter().filter(|row| row.name.ends_with(&suffix));
+    match (found.next(), found.count()) {
+        (Some(row), 0) => Ok(row),
+        (Some(_), n) => Err(format!("`{name}` could be any of {} signals", n + 1)),
+        (None, _) => Err(format!("no signal `{name}` shown")),
+    }
+}
+
+/// Searching the selected row for a value, listing every time it has it.
+#[derive(Default)]
+struct FindPanel {
+    text: String,
+    // the name of the row the last search was of and the times it found
+    results: Option<(String, Vec<u64>)>,
+}
+
+impl FindPanel {
+    /// Show the panel, giving the time to move the cursor to. Matches are listed from `range`.
+    fn show(
+        &mut self,
+        ui: &mut Ui,
+        row: Option<&WaveRow>,
+        cursor: Option<u64>,
+        range: std::ops::RangeInclusive<u64>,
+        timescale: Option<vcd::Timescale>,
+    ) -> Option<u64> {
+        let mut goto = None;
+        ui.heading("Find value");
+        let signal = row
+            .and_then(|row| row.signal.as_ref())
+            .filter(|s| !s.is_real() && !s.is_string());
+        match (row, signal) {
+            (Some(row), Some(_)) => ui.label(format!("in {}", row.name)),
+            (Some(row), None) => ui.weak(format!("{} can't be searched", row.name)),
+            (None, _) => ui.weak("click a row to search it"),
+        };
+        let resp = ui.add(
+            egui::TextEdit::singleline(&mut self.text)
+                .code_editor()
+                .hint_text("8'b1x0x_xxxx"),
+        );
+        let enter = resp.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+        ui.label(
+            RichText::new("e.g. 'h3f, 0x3f, 63 or 0b11_1111, x bits match anything")
+                .small()
+                .weak(),
+        );
+        let pattern = match signal {
+            Some(signal) if !self.text.trim().is_empty() => {
+                match search::Pattern::parse(&self.text, signal.width()) {
+                    Ok(pattern) => Some((signal, pattern)),
+                    Err(err) => {
+                        ui.colored_label(ui.visuals().error_fg_color, err.to_string());
+                        None
+                    }
+                }
+            }
+            _ => None,
+        };
+
+        let (prev, next, find_all) = ui
+            .horizontal(|ui| {
+                let enabled = pattern.is_some();
+                let prev = ui.add_enabled(enabled, egui::Button::new("⏴ Previous"));
+                let next = ui.add_enabled(enabled, egui::Button::new("Next ⏵"));
+                let all = ui.add_enabled(enabled, egui::Button::new("Find all"));
+                (prev.clicked(), next.clicked(), all.clicked())
+            })
+            .inner;
+        if let (Some((signal, pattern)), Some(row)) = (&pattern, row) {
+            if prev {
+                goto = pattern.prev(signal, cursor.unwrap_or(u64::MAX));
+            }
+            // enter finds them all and moves on to the next one
+            if next || enter {
+                goto = pattern.next(signal, cursor.unwrap_or(0));
+            }
+            if find_all || enter {
+                self.results = Some((row.name.clone(), pattern.find_all(signal, range)));
+            }
+        }
+
+        let Some((name, times)) = &self.results else {
+            return goto;
+        };
+        ui.separator();
+        let s = if times.len() == 1 { "" } else { "es" };
+        ui.label(format!("{} match{s} in {name}", times.len()));
+        // the values are shown while the row searched is still the one selected
+        let signal = row.filter(|row| row.name == *name).and(signal);
+        let row_height = ui.text_style_height(&egui::TextStyle::Body);
+        egui::ScrollArea::vertical()
+            .auto_shrink([false; 2])
+            .show_rows(ui, row_height, times.len(), |ui, rows| {
+                for &t in &times[rows] {
+                    let mut text = format_time(timescale, t);
+                    if let Some(value) = signal.and_then(|s| s.value_at(t)) {
+                        let radix = row.map_or(vcd::Radix::Hex, |row| row.radix);
+                        text = format!("{text}  {}", radix.format(&value));
+                    }
+                    if ui.selectable_label(cursor == Some(t), text).clicked() {
+                        goto = Some(t);
+                    }
+                }
+            });
+        goto
+    }
+}
+
+/// A time in the file's units, or in ticks if it doesn't have any.
+fn format_time(timescale: Option<vcd::Timescale>, t: u64) -> String {
+    match timescale {
+        Some(timescale) => timescale.format(t as i64),
+        None => t.to_string(),
+    }
+}
+
+/// A length of time that may be between ticks, like an average.
+fn format_duration(timescale: Option<vcd::Timescale>, ticks: f64) -> String {
+    match timescale {
+        // thousandths of a tick so the fraction isn't lost
+        Some(timescale) => vcd::Timescale {
+            exponent: timescale.exponent - 3,
+            ..timescale
+        }
+        .format((ticks * 1000.0).round() as i64),
+        None => format!("{}", (ticks * 1000.0).round() / 1000.0),
+    }
+}
+
+/// The frequency of something happening every `period` ticks.
+fn format_frequency(timescale: Option<vcd::Timescale>, period: f64) -> String {
+    let Some(timescale) = timescale else {
+        return format!("{:.4} per tick", 1.0 / period);
+    };
+    let hz = 1.0 / (period * timescale.seconds());
+    let (div, unit) = [(1e12, "THz"), (1e9, "GHz"), (1e6, "MHz"), (1e3, "kHz")]
+        .into_iter()
+        .find(|(div, _)| hz >= *div)
+        .unwrap_or((1.0, "Hz"));
+    let value = format!("{:.3}", hz / div);
+    let value = value.trim_end_matches('0').trim_end_matches('.');
+    format!("{value} {unit}")
+}
+
+enum Stats {
+    Bit(stats::BitStats),
+    Bus(stats::BusStats),
+}
+
+/// Statistics of the selected row over the selection, or the whole file.
+#[derive(Default)]
+struct StatsPanel {
+    // what the stats were worked out for so they're only redone when it changes: the row, the
+    // range, and where its signal is and how long it is since a followed file grows in place
+    key: Option<(egui::Id, std::ops::Range<u64>, usize, u64)>,
+    stats: Option<Stats>,
+}
+
+impl StatsPanel {
+    // the most values shown in a histogram
+    const MAX_VALUES: usize = 64;
+
+    fn show(
+        &mut self,
+        ui: &mut Ui,
+        row: Option<&WaveRow>,
+        range: std::ops::Range<u64>,
+        timescale: Option<vcd::Timescale>,
+    ) {
+        ui.heading("Statistics");
+        let Some(row) = row else {
+            ui.weak("click a row to see its statistics");
+            return;
+        };
+        let Some(signal) = row
+            .signal
+            .as_ref()
+            .filter(|s| !s.is_real() && !s.is_string())
+        else {
+            ui.weak(format!("{} has no statistics", row.name));
+            return;
+        };
+        let key = (
+            row.id(),
+            range.clone(),
+            Arc::as_ptr(signal) as usize,
+            signal.final_time(),
+        );
+        if self.key.as_ref() != Some(&key) {
+            self.stats = Some(if signal.width() == 1 {
+                Stats::Bit(stats::BitStats::new(signal, range.clone()))
+            } else {
+                Stats::Bus(stats::BusStats::new(signal, range.clone()))
+            });
+            self.key = Some(key);
+        }
+        ui.label(format!(
+            "{} from {} to {}",
+            row.name,
+            format_time(timescale, range.start),
+            format_time(timescale, range.end)
+        ));
+        ui.separator();
+
+        let time = |ticks: u64, duration: u64| {
+            let percent = 100.0 * ticks as f64 / duration.max(1) as f64;
+            format!("{percent:.1}% ({})", format_time(timescale, ticks))
+        };
+        let widths = |ui: &mut Ui, name: &str, widths: Option<stats::Widths>| {
+            ui.label(name);
+            match widths {
+                Some(w) => ui.label(format!(
+                    "{} / {} / {} ({} pulses)",
+                    format_time(timescale, w.min),
+                    format_time(timescale, w.max),
+                    format_duration(timescale, w.mean()),
+                    w.count
+                )),
+                None => ui.weak("none"),
+            };
+            ui.end_row();
+        };
+        match self.stats.as_ref().unwrap() {
+            Stats::Bit(stats) => {
+                egui::Grid::new("bit_stats").num_columns(2).show(ui, |ui| {
+                    ui.label("Transitions");
+                    ui.label(stats.transitions.to_string());
+                    ui.end_row();
+                    for (name, ticks) in ["Low", "High", "X", "Z"].into_iter().zip(stats.time_at) {
+                        ui.label(name);
+                        ui.label(time(ticks, stats.duration));
+                        ui.end_row();
+                    }
+                    ui.weak("Pulses");
+                    ui.weak("min / max / average");
+                    ui.end_row();
+                    widths(ui, "High", stats.high);
+                    widths(ui, "Low", stats.low);
+                    if let Some(clock) = &stats.clock {
+                        let mean = clock.periods.mean();
+                        ui.label("Frequency");
+                        ui.label(format_frequency(timescale, mean));
+                        ui.end_row();
+                        ui.label("Period");
+                        ui.label(format_duration(timescale, mean));
+                        ui.end_row();
+                        ui.label("Jitter");
+                        ui.label(format!(
+                            "{} rms, {} peak to peak",
+                            format_duration(timescale, clock.jitter),
+                            format_time(timescale, clock.periods.max - clock.periods.min)
+                        ))
+                        .on_hover_text("How much the times between rising edges vary");
+                        ui.end_row();
+                    }
+                });
+            }
+            Stats::Bus(stats) => {
+                egui::Grid::new("bus_stats").num_columns(2).show(ui, |ui| {
+                    ui.label("Transitions");
+                    ui.label(stats.transitions.to_string());
+                    ui.end_row();
+                    ui.label("Distinct values");
+                    ui.label(stats.values.len().to_string());
+                    ui.end_row();
+                });
+                ui.separator();
+                let most = stats.values.first().map_or(1, |v| v.count);
+                egui::ScrollArea::vertical()
+                    .auto_shrink([false; 2])
+                    .show(ui, |ui| {
+                        egui::Grid::new("bus_histogram")
+                            .num_columns(3)
+                            .show(ui, |ui| {
+                                for value in stats.values.iter().take(Self::MAX_VALUES) {
+                                    let text = match signal.label(&value.value) {
+                                        Some(label) => label.to_owned(),
+                                        None => row.radix.format(&value.value),
+                                    };
+                                    ui.monospace(text);
+                                    ui.add(
+                                        egui::ProgressBar::new(value.count as f32 / most as f32)
+                                            .desired_width(120.0)
+                                            .text(value.count.to_string()),
+                                    );
+                                    ui.label(time(value.time, stats.duration));
+                                    ui.end_row();
+                                }
+                            });
+                        let others = stats.values.len().saturating_sub(Self::MAX_VALUES);
+                        if others > 0 {
+                            ui.weak(format!("and {others} less common values"));
+                        }
+                    });
+            }
+        }
+    }
+}
+
 struct UrlWindow {
     url: String,
     open: bool,
@@ -342,20 +1863,54 @@ impl eframe::App for TemplateApp {
 
     /// Called each time the UI needs repainting, which may be many times per second.
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.show_loading(ctx);
+        self.finish_signal_loading(ctx);
+        self.update_following(ctx);
+        for row in &mut self.wave_data {
+            row.derive();
+        }
+
         let Self {
             wave_data,
+            file_vars,
+            file_signals,
+            scope_tree,
             final_time,
+            timescale,
+            not_recorded,
+            raw_ticks,
+            lazy_loading,
+            follow_scroll,
+            #[cfg(not(target_arch = "wasm32"))]
+            path,
+            reloading,
             x_scale,
             x_offset,
             y_offset,
             drag_time_start,
+            selection,
+            cursor,
+            selected,
             dropped_files: _,
             main_viewport,
             a_future,
+            a_save,
             open_file_ctx,
+            loading,
+            index,
+            signal_loading,
+            following,
             download,
             url_window,
             err_window,
+            warnings_window,
+            export_window,
+            import_window,
+            slice_window,
+            concat_window,
+            expr_window,
+            find_panel,
+            stats_panel,
             row_height,
             side_panel,
             info,
@@ -381,21 +1936,21 @@ impl eframe::App for TemplateApp {
                         res.status,
                         res.headers
                     );
-                    let bytes = &res.bytes;
-                    let mut cursor = std::io::Cursor::new(bytes);
 
                     if res.status == 200 {
-                        match vcd::read_clocked_vcd(&mut cursor) {
-                            Ok((signals, time)) => {
-                                *wave_data = mk_wave_data(signals);
-                                *final_time = time;
-                                *x_scale = None;
-                            }
-                            Err(err) => {
-                                err_window.msg =
-                                    format!("url {} failed to parse as vcd:\n{err}", res.url);
-                                err_window.open = true;
-                            }
+                        let Download::Done(Ok(res)) = std::mem::replace(&mut *dl, Download::None)
+                        else {
+                            unreachable!()
+                        };
+                        if let Some(old) =
+                            loading.replace(load::Loading::from_bytes(res.url, res.bytes))
+                        {
+                            old.cancel();
+                        }
+                        *reloading = false;
+                        #[cfg(not(target_arch = "wasm32"))]
+                        {
+                            *path = None;
                         }
                     } else {
                         err_window.msg =
@@ -407,6 +1962,13 @@ impl eframe::App for TemplateApp {
             }
         }
 
+        if (a_future.is_some() || a_save.is_some()) && open_file_ctx.is_none() {
+            let awoken = Arc::new(AtomicBool::new(false));
+            *open_file_ctx = Some(OpenFileCtx {
+                awoken,
+                egui_ctx: ctx.clone(),
+            });
+        }
         if let Some(future) = a_future {
             if open_file_ctx.is_none() {
                 let awoken = Arc::new(AtomicBool::new(false));
@@ -421,21 +1983,78 @@ impl eframe::App for TemplateApp {
             match Future::poll(future.as_mut(), &mut my_ctx) {
                 Poll::Pending => (),
                 Poll::Ready(shandle) => {
-                    if let Some(handle) = shandle {
-                        *wave_data = handle.wave_data;
-                        *final_time = handle.time;
+                    if let Some(opened) = shandle {
+                        let new = match opened {
+                            #[cfg(not(target_arch = "wasm32"))]
+                            OpenedVcd::Path(opened) => {
+                                *path = Some(opened.clone());
+                                Some(load::Loading::from_path(opened, *lazy_loading))
+                            }
+                            #[cfg(not(target_arch = "wasm32"))]
+                            OpenedVcd::Follow(followed) => {
+                                if let Some(old) = loading.take() {
+                                    old.cancel();
+                                }
+                                *following = Some(load::Following::from_path(followed));
+                                *path = None;
+                                None
+                            }
+                            #[cfg(target_arch = "wasm32")]
+                            OpenedVcd::Bytes { filename, bytes } => {
+                                Some(load::Loading::from_bytes(filename, bytes))
+                            }
+                            OpenedVcd::WaveDrom { filename, bytes } => {
+                                // line the cells up with a clock if there's one
+                                let times = 0..*final_time + 1;
+                                let tick = wave_data
+                                    .iter()
+                                    .filter_map(|row| row.signal.as_ref())
+                                    .find_map(|s| wavedrom::clock_period(s, times.clone()));
+                                *import_window = ImportWindow {
+                                    open: true,
+                                    name: filename,
+                                    text: String::from_utf8_lossy(&bytes).into_owned(),
+                                    start: selection.as_ref().map_or(0, |s| *s.start()),
+                                    tick: tick.map_or(1, |(period, _)| period),
+                                };
+                                None
+                            }
+                        };
+                        if new.is_some() {
+                            *reloading = false;
+                        }
+                        if let Some(old) = new.and_then(|new| loading.replace(new)) {
+                            old.cancel();
+                        }
                     }
                     *a_future = None;
-                    *open_file_ctx = None;
                 }
             }
         }
+        if let Some(future) = a_save {
+            let waker =
+                unsafe { std::task::Waker::from_raw(new_waker(open_file_ctx.as_ref().unwrap())) };
+            let mut my_ctx = std::task::Context::from_waker(&waker);
+            if let Poll::Ready(saved) = Future::poll(future.as_mut(), &mut my_ctx) {
+                if let Some(Err(err)) = saved {
+                    err_window.msg = format!("failed to save:\n{err}");
+                    err_window.open = true;
+                }
+                *a_save = None;
+            }
+        }
+        if a_future.is_none() && a_save.is_none() {
+            *open_file_ctx = None;
+        }
 
         // Examples of how to create different panels and windows.
         // Pick whichever suits you.
         // Tip: a good default choice is to just keep the `CentralPanel`.
         // For inspiration and more examples, go to https://emilk.github.io/egui
 
+        // the cursor movement asked for by the buttons or keys, and the time it goes to
+        let mut jump = None;
+        let mut goto = None;
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
             // The top panel is often a good place for a menu bar:
             egui::menu::bar(ui, |ui| {
@@ -445,34 +2064,104 @@ impl eframe::App for TemplateApp {
                     if ui.button("Open File…").clicked() {
                         *a_future = Some(Box::pin(async {
                             let handle = rfd::AsyncFileDialog::new().pick_file().await;
-                            if let Some(h) = &handle {
-                                let bytes = h.read().await;
-                                let mut cursor = std::io::Cursor::new(&bytes);
-                                let (signals, time) = vcd::read_clocked_vcd(&mut cursor).unwrap();
-                                let wave_data = mk_wave_data(signals);
-                                Some(OpenedVcd {
-                                    // filename: h.file_name(),
-                                    wave_data,
-                                    time,
-                                })
-                            } else {
-                                None
-                            }
+                            let h = handle?;
+                            #[cfg(not(target_arch = "wasm32"))]
+                            return Some(OpenedVcd::Path(h.path().to_owned()));
+                            #[cfg(target_arch = "wasm32")]
+                            return Some(OpenedVcd::Bytes {
+                                filename: h.file_name(),
+                                bytes: h.read().await,
+                            });
+                        }));
+                        ui.close_menu();
+                        ctx.request_repaint();
+                    }
+                    #[cfg(not(target_arch = "wasm32"))]
+                    if ui.button("Follow File…").clicked() {
+                        *a_future = Some(Box::pin(async {
+                            let handle = rfd::AsyncFileDialog::new().pick_file().await;
+                            Some(OpenedVcd::Follow(handle?.path().to_owned()))
+                        }));
+                        ui.close_menu();
+                        ctx.request_repaint();
+                    }
+                    if ui.button("Import WaveDrom…").clicked() {
+                        *a_future = Some(Box::pin(async {
+                            let handle = rfd::AsyncFileDialog::new()
+                                .add_filter("WaveJSON", &["json", "json5", "js"])
+                                .pick_file()
+                                .await?;
+                            Some(OpenedVcd::WaveDrom {
+                                filename: handle.file_name(),
+                                bytes: handle.read().await,
+                            })
                         }));
                         ui.close_menu();
                         ctx.request_repaint();
                     }
+                    #[cfg(not(target_arch = "wasm32"))]
+                    if ui
+                        .add_enabled(path.is_some(), egui::Button::new("Reload"))
+                        .on_hover_text("Read the file again, keeping the signals shown")
+                        .clicked()
+                    {
+                        let opened = path.clone().unwrap();
+                        // rows made from others are made again from the new signals
+                        if let Some(old) =
+                            loading.replace(load::Loading::from_path(opened, *lazy_loading))
+                        {
+                            old.cancel();
+                        }
+                        *reloading = true;
+                        ui.close_menu();
+                    }
                     if ui.button("Open URL…").clicked() {
                         url_window.open = true;
                         ui.close_menu();
                     }
+                    if ui
+                        .add_enabled(!wave_data.is_empty(), egui::Button::new("Export…"))
+                        .clicked()
+                    {
+                        let shown = wave_data
+                            .iter()
+                            .map(|d| d.name.contains(&*search_text))
+                            .collect();
+                        let range = selection.clone().unwrap_or(0..=*final_time);
+                        export_window.open(shown, range);
+                        ui.close_menu();
+                    }
+                    // web files only come as bytes so are always read in full
+                    ui.add_enabled(
+                        cfg!(not(target_arch = "wasm32")),
+                        egui::Checkbox::new(lazy_loading, "Load signals on demand"),
+                    )
+                    .on_hover_text("Only index VCD and FST files, reading signals when shown");
                     if ui.button("Reset").clicked() {
+                        if let Some(old) = signal_loading.take() {
+                            old.cancel();
+                        }
+                        *index = None;
+                        *following = None;
+                        #[cfg(not(target_arch = "wasm32"))]
+                        {
+                            *path = None;
+                        }
                         *wave_data = vec![];
+                        file_vars.clear();
+                        file_signals.clear();
+                        *scope_tree = scopes::ScopeTree::default();
                         *final_time = 1;
+                        *timescale = None;
+                        not_recorded.clear();
                         *x_scale = None;
                         *x_offset = None;
                         *y_offset = 0.0;
                         *drag_time_start = None;
+                        *selection = None;
+                        *cursor = None;
+                        *selected = None;
+                        find_panel.results = None;
                         *search_text = String::new();
                         ui.close_menu();
                     }
@@ -481,8 +2170,59 @@ impl eframe::App for TemplateApp {
                         ctx.send_viewport_cmd(ViewportCommand::Close);
                     }
                 });
+                ui.menu_button("Signals", |ui| {
+                    if ui
+                        .add_enabled(!wave_data.is_empty(), egui::Button::new("Concatenate…"))
+                        .clicked()
+                    {
+                        concat_window.open();
+                        ui.close_menu();
+                    }
+                    if ui
+                        .add_enabled(!wave_data.is_empty(), egui::Button::new("Add expression…"))
+                        .on_hover_text("A signal made from others, like valid && ready")
+                        .clicked()
+                    {
+                        expr_window.open();
+                        ui.close_menu();
+                    }
+                    if ui
+                        .button("Find value…")
+                        .on_hover_text("Search the selected row for a value, like an address")
+                        .clicked()
+                    {
+                        *side_panel = SidePanel::Find;
+                        ui.close_menu();
+                    }
+                    if ui
+                        .button("Statistics…")
+                        .on_hover_text("Duty cycle, pulse widths or values of the selected row")
+                        .clicked()
+                    {
+                        *side_panel = SidePanel::Stats;
+                        ui.close_menu();
+                    }
+                    let groups = bus_groups(wave_data).len();
+                    if ui
+                        .add_enabled(groups > 0, egui::Button::new("Group bus bits"))
+                        .on_hover_text("Show signals like data[0], data[1]… as one bus")
+                        .clicked()
+                    {
+                        group_bus_bits(wave_data);
+                        ui.close_menu();
+                    }
+                });
                 ui.menu_button("View", |ui| {
                     ui.add(egui::Slider::new(row_height, 25.0..=128.0).text("height"));
+                    ui.add_enabled(
+                        timescale.is_some(),
+                        egui::Checkbox::new(raw_ticks, "Show raw ticks"),
+                    );
+                    ui.add_enabled(
+                        following.is_some(),
+                        egui::Checkbox::new(follow_scroll, "Scroll to newest"),
+                    )
+                    .on_hover_text("Keep the end of a followed file in view as it grows");
                     // if *show_info {
                     //     if ui.button("Hide info").clicked() {
                     //         *show_info = false;
@@ -492,44 +2232,83 @@ impl eframe::App for TemplateApp {
                     //     *show_info = true;
                     //     ui.close_menu();
                     // }
-                    match side_panel {
-                        SidePanel::None => {
-                            if ui.button("Show info").clicked() {
-                                *side_panel = SidePanel::Info;
-                                ui.close_menu();
-                            }
-                            if ui.button("Show samples").clicked() {
-                                *side_panel = SidePanel::Samples;
-                                ui.close_menu();
-                            }
-                        }
-                        SidePanel::Info => {
-                            if ui.button("Hide info").clicked() {
-                                *side_panel = SidePanel::None;
-                                ui.close_menu();
-                            }
-                            if ui.button("Show samples").clicked() {
-                                *side_panel = SidePanel::Samples;
-                                ui.close_menu();
-                            }
-                        }
-                        SidePanel::Samples => {
-                            if ui.button("Show info").clicked() {
-                                *side_panel = SidePanel::Info;
-                                ui.close_menu();
-                            }
-                            if ui.button("Hide samples").clicked() {
-                                *side_panel = SidePanel::None;
-                                ui.close_menu();
-                            }
+                    for (panel, name) in [
+                        (SidePanel::Scopes, "scopes"),
+                        (SidePanel::Find, "value search"),
+                        (SidePanel::Stats, "statistics"),
+                        (SidePanel::Info, "info"),
+                        (SidePanel::Samples, "samples"),
+                    ] {
+                        let shown = *side_panel == panel;
+                        let text = if shown { "Hide" } else { "Show" };
+                        if ui.button(format!("{text} {name}")).clicked() {
+                            *side_panel = if shown { SidePanel::None } else { panel };
+                            ui.close_menu();
                         }
                     }
 
                     // ui.button("
                 });
+                ui.separator();
+
+                let signal = selected
+                    .and_then(|id| wave_data.iter().find(|d| d.id() == id))
+                    .and_then(|d| d.signal.as_ref());
+                for j in Jump::ALL {
+                    let enabled = signal.is_some_and(|s| j.applies(s));
+                    if ui
+                        .add_enabled(enabled, egui::Button::new("⏴").small())
+                        .on_hover_text(format!(
+                            "Previous {} ({})",
+                            j.name(),
+                            ctx.format_shortcut(&j.shortcut(false))
+                        ))
+                        .clicked()
+                    {
+                        jump = Some((j, false));
+                    }
+                    ui.label(j.name());
+                    if ui
+                        .add_enabled(enabled, egui::Button::new("⏵").small())
+                        .on_hover_text(format!(
+                            "Next {} ({})",
+                            j.name(),
+                            ctx.format_shortcut(&j.shortcut(true))
+                        ))
+                        .clicked()
+                    {
+                        jump = Some((j, true));
+                    }
+                }
+                if signal.is_none() {
+                    ui.label(RichText::new("click a row to move along it").weak());
+                } else if let Some(t) = *cursor {
+                    let t = format_time(timescale.filter(|_| !*raw_ticks), t);
+                    ui.label(format!("cursor {t}"));
+                }
             });
         });
 
+        // move the cursor along the selected row, unless the keys are for a text box. The plain
+        // arrows match with modifiers held too so they're checked last.
+        if ctx.memory(|m| m.focused().is_none()) {
+            for j in Jump::ALL.into_iter().rev() {
+                for forward in [false, true] {
+                    if ctx.input_mut(|i| i.consume_shortcut(&j.shortcut(forward))) {
+                        jump = Some((j, forward));
+                    }
+                }
+            }
+        }
+        if let Some((j, forward)) = jump {
+            let signal = selected
+                .and_then(|id| wave_data.iter().find(|d| d.id() == id))
+                .and_then(|d| d.signal.as_ref())
+                .filter(|s| j.applies(s));
+            let from = cursor.unwrap_or(if forward { 0 } else { u64::MAX });
+            goto = signal.and_then(|s| j.find(s, from, forward));
+        }
+
         // if *show_info {
         //     egui::SidePanel::right("inspection_panel").show(ctx, |ui| {
         //         let scroll_area = egui::ScrollArea::both().auto_shrink([false; 2]);
@@ -538,6 +2317,63 @@ impl eframe::App for TemplateApp {
         // }
         match side_panel {
             SidePanel::None => (),
+            SidePanel::Scopes => {
+                egui::SidePanel::right("inspection_panel").show(ctx, |ui| {
+                    let groups = bus_groups(wave_data).len();
+                    if groups > 0 {
+                        ui.horizontal(|ui| {
+                            let buses = if groups == 1 { "bus" } else { "buses" };
+                            ui.label(format!("{groups} {buses} shown as bits"));
+                            if ui.button("Group").clicked() {
+                                group_bus_bits(wave_data);
+                            }
+                        });
+                        ui.separator();
+                    }
+                    let scroll_area = egui::ScrollArea::both().auto_shrink([false; 2]);
+                    let add = scroll_area.show(ui, |ui| {
+                        if scope_tree.is_empty() {
+                            ui.weak("no file open");
+                        }
+                        scope_tree.ui(ui, file_vars)
+                    });
+                    let rows = mk_wave_data(
+                        add.inner
+                            .into_iter()
+                            .map(|i| (&file_vars[i], file_signals[i].clone())),
+                    );
+                    for row in rows {
+                        // a var can only be shown once
+                        let shown = wave_data
+                            .iter()
+                            .any(|r| r.file_code() == row.file_code() && r.name == row.name);
+                        if !shown {
+                            wave_data.push(row);
+                        }
+                    }
+                });
+            }
+            SidePanel::Find => {
+                egui::SidePanel::right("inspection_panel").show(ctx, |ui| {
+                    let row = selected.and_then(|id| wave_data.iter().find(|d| d.id() == id));
+                    let range = selection.clone().unwrap_or(0..=*final_time);
+                    let shown_timescale = timescale.filter(|_| !*raw_ticks);
+                    if let Some(t) = find_panel.show(ui, row, *cursor, range, shown_timescale) {
+                        goto = Some(t);
+                    }
+                });
+            }
+            SidePanel::Stats => {
+                egui::SidePanel::right("inspection_panel").show(ctx, |ui| {
+                    let row = selected.and_then(|id| wave_data.iter().find(|d| d.id() == id));
+                    let range = match selection {
+                        Some(selection) => *selection.start()..*selection.end(),
+                        None => 0..*final_time,
+                    };
+                    let shown_timescale = timescale.filter(|_| !*raw_ticks);
+                    stats_panel.show(ui, row, range, shown_timescale);
+                });
+            }
             SidePanel::Info => {
                 egui::SidePanel::right("inspection_panel").show(ctx, |ui| {
                     let scroll_area = egui::ScrollArea::both().auto_shrink([false; 2]);
@@ -564,6 +2400,20 @@ impl eframe::App for TemplateApp {
             }
         }
 
+        if let Some(t) = goto {
+            *cursor = Some(t);
+            // scroll to put the cursor in the middle if it's gone out of view
+            if let Some(x_scale) = *x_scale {
+                let x = t as f32 * x_scale * 32.0;
+                if !main_viewport.x_range().contains(x) {
+                    let width = *final_time as f32 * x_scale * 32.0;
+                    let max_offset = (width - main_viewport.width()).max(0.0);
+                    let offset = x - main_viewport.width() / 2.0;
+                    *x_offset = Some(offset.clamp(0.0, max_offset));
+                }
+            }
+        }
+
         // let main_viewport = std::rc::Rc::new(std::cell::Cell::new(None));
         // let mut main_viewport = None;
 
@@ -614,17 +2464,16 @@ impl eframe::App for TemplateApp {
 
             // let rect = egui::Rect::from_x_y_ranges(ui.max_rect().x_range(), y_min..=y_max);
 
-            use egui_dnd::DragDropItem;
-
+            let mut remove = None;
             // TODO fix this changing x scroll in main window
             egui_dnd::dnd(&mut ui, "dnd").show_custom_vec(wave_data, |ui, wave_data, iter| {
                 // 32 looks better with default layout but 25 looks better with top_down/centered
                 // ui.horizontal(|ui| ui.set_height(32.0 + row_height_with_spacing * min_row as f32));
                 ui.horizontal(|ui| ui.set_height(25.0 + row_height_with_spacing * min_row as f32));
                 for (i, d) in wave_data
-                    .iter()
+                    .iter_mut()
                     .enumerate()
-                    .filter(|(_, d)| d.0.contains(&*search_text))
+                    .filter(|(_, d)| d.name.contains(&*search_text))
                     .take(max_row)
                     .skip(min_row)
                 {
@@ -636,7 +2485,59 @@ impl eframe::App for TemplateApp {
                                 handle.ui(ui, |ui| {
                                     ui.with_layout(Layout::top_down(egui::Align::Max), |ui| {
                                         ui.horizontal_centered(|ui| {
-                                            ui.label(&d.0);
+                                            ui.label(
+                                                RichText::new(d.var.var.var_type.to_string())
+                                                    .small()
+                                                    .weak(),
+                                            );
+                                            let mut name = RichText::new(&d.name);
+                                            if *selected == Some(d.id()) {
+                                                name = name.strong();
+                                            }
+                                            let resp = ui
+                                                .add(Label::new(name).sense(Sense::click()))
+                                                .on_hover_text("Click to move the cursor along");
+                                            if resp.clicked() {
+                                                *selected = Some(d.id());
+                                            }
+                                            resp.context_menu(|ui| {
+                                                match &d.signal {
+                                                    Some(s) if s.is_real() => {
+                                                        ui.label("Interpolation");
+                                                        ui.radio_value(
+                                                            &mut d.interpolation,
+                                                            wave::Interpolation::Step,
+                                                            "Step",
+                                                        );
+                                                        ui.radio_value(
+                                                            &mut d.interpolation,
+                                                            wave::Interpolation::Linear,
+                                                            "Linear",
+                                                        );
+                                                        ui.separator();
+                                                    }
+                                                    Some(s) if !s.is_string() && s.width() > 1 => {
+                                                        ui.label("Radix");
+                                                        for radix in vcd::Radix::ALL {
+                                                            ui.radio_value(
+                                                                &mut d.radix,
+                                                                radix,
+                                                                radix.name(),
+                                                            );
+                                                        }
+                                                        ui.separator();
+                                                        if ui.button("Slice…").clicked() {
+                                                            slice_window.open(d);
+                                                            ui.close_menu();
+                                                        }
+                                                    }
+                                                    _ => (),
+                                                }
+                                                if ui.button("Remove").clicked() {
+                                                    remove = Some(i);
+                                                    ui.close_menu();
+                                                }
+                                            });
                                         });
                                     });
                                 });
@@ -645,8 +2546,14 @@ impl eframe::App for TemplateApp {
                     });
                 }
             });
+            if let Some(i) = remove {
+                wave_data.remove(i);
+            }
         });
 
+        // rows shown before their signal has been read
+        let mut unread = vec![];
+
         egui::CentralPanel::default().show(ctx, |ui| {
             // The central panel the region left after adding TopPanel's and SidePanel's
             // ui.heading("eframe template");
@@ -665,7 +2572,7 @@ impl eframe::App for TemplateApp {
 
             let filtered = wave_data
                 .iter()
-                .filter(|(name, _)| name.contains(&*search_text))
+                .filter(|d| d.name.contains(&*search_text))
                 .collect::<Vec<_>>();
 
             let num_rows = filtered.len();
@@ -737,13 +2644,51 @@ impl eframe::App for TemplateApp {
                         // });
                         ui.vertical(|ui| {
                             for d in filtered.iter().take(max_row).skip(min_row) {
+                                if *selected == Some(d.id()) {
+                                    let top = ui.cursor().min.y;
+                                    ui.painter().rect_filled(
+                                        egui::Rect::from_x_y_ranges(
+                                            ui.clip_rect().x_range(),
+                                            top..=top + *row_height,
+                                        ),
+                                        egui::Rounding::ZERO,
+                                        ui.visuals().selection.bg_fill.linear_multiply(0.2),
+                                    );
+                                }
+                                let Some(signal) = &d.signal else {
+                                    // keep the space until it's been read, or say why it can't
+                                    // be made
+                                    let width = *final_time as f32 * *x_scale * 32.0;
+                                    let (rect, _) = ui.allocate_exact_size(
+                                        vec2(width, *row_height),
+                                        Sense::hover(),
+                                    );
+                                    let (text, color) = match &d.error {
+                                        Some(err) => (err.as_str(), ui.visuals().error_fg_color),
+                                        None => {
+                                            d.unread(&mut unread);
+                                            ("loading…", ui.visuals().weak_text_color())
+                                        }
+                                    };
+                                    ui.painter().text(
+                                        pos2(ui.clip_rect().min.x.max(rect.min.x), rect.center().y),
+                                        Align2::LEFT_CENTER,
+                                        text,
+                                        FontId::default(),
+                                        color,
+                                    );
+                                    continue;
+                                };
                                 let mut wave = wave::Wave::new(
-                                    &d.0,
+                                    &d.name,
                                     *x_scale,
                                     viewport.min.x..=viewport.max.x,
-                                    &d.1,
+                                    signal,
                                 );
                                 wave.height = *row_height;
+                                wave.interpolation = d.interpolation;
+                                wave.radix = d.radix;
+                                wave.not_recorded = not_recorded;
                                 wave.ui(ui);
                             }
                         });
@@ -785,7 +2730,28 @@ impl eframe::App for TemplateApp {
 
                 let yellow = egui::Color32::from_rgb(0xd2, 0x99, 0x1d);
 
+                if let Some(t) = *cursor {
+                    let x = rect.min.x + t as f32 * *x_scale * 32.0;
+                    ui.painter().line_segment(
+                        [pos2(x, max_rect.min.y + 16.0), pos2(x, max_rect.max.y)],
+                        egui::Stroke::new(2.0, ui.visuals().selection.stroke.color),
+                    );
+                }
+
                 let mut hover_t = None;
+                // the selection stays shaded once the drag is done
+                if let (None, Some(selection)) = (*drag_time_start, &*selection) {
+                    let x0 = rect.min.x + *selection.start() as f32 * *x_scale * 32.0;
+                    let x1 = rect.min.x + *selection.end() as f32 * *x_scale * 32.0;
+                    ui.painter().rect_filled(
+                        egui::Rect::from_x_y_ranges(
+                            x0..=x1,
+                            max_rect.min.y + 16.0..=max_rect.max.y,
+                        ),
+                        egui::Rounding::ZERO,
+                        yellow.linear_multiply(0.1),
+                    );
+                }
                 if let Some(pos) = &wave_resp.hover_pos() {
                     use egui::*;
                     let mut shapes = vec![];
@@ -825,7 +2791,23 @@ impl eframe::App for TemplateApp {
                 }
 
                 if wave_resp.drag_stopped() {
+                    if let (Some(start), Some(end)) = (*drag_time_start, hover_t) {
+                        let (start, end) = (start.min(end) as u64, start.max(end) as u64);
+                        *selection = (start != end).then_some(start..=end);
+                    }
                     *drag_time_start = None;
+                } else if wave_resp.clicked() {
+                    *selection = None;
+                    // put the cursor there and select the row clicked on to move it along
+                    *cursor = hover_t.map(|t| t as u64);
+                    if let Some(pos) = wave_resp.interact_pointer_pos() {
+                        let row = (pos.y - rect.min.y) / row_height_with_spacing;
+                        if row >= 0.0 {
+                            if let Some(d) = filtered.get(min_row + row as usize) {
+                                *selected = Some(d.id());
+                            }
+                        }
+                    }
                 }
 
                 let rect = egui::Rect::from_x_y_ranges(ui.max_rect().x_range(), y_min..=16.0);
@@ -833,9 +2815,20 @@ impl eframe::App for TemplateApp {
                 let x_max = (main_viewport.max.x / 32.0 / *x_scale).ceil() as usize;
                 let mut ticks = vec![];
                 let stroke = egui::Stroke::new(2.0, yellow);
-                let num_ticks = std::cmp::max(1, (main_viewport.width() / 64.0).floor() as usize);
-                let gap = std::cmp::max(
-                    1,
+                let shown_timescale = timescale.filter(|_| !*raw_ticks);
+                let format_time = |t: i64| match shown_timescale {
+                    Some(timescale) => timescale.format(t),
+                    None => t.to_string(),
+                };
+                // physical times need more room for the unit
+                let tick_width = if shown_timescale.is_some() {
+                    96.0
+                } else {
+                    64.0
+                };
+                let num_ticks =
+                    std::cmp::max(1, (main_viewport.width() / tick_width).floor() as usize);
+                let gap = nice_gap(
                     (main_viewport.width() / 32.0 / *x_scale / num_ticks as f32).round() as usize,
                 );
                 // render the previous tick because part of it is still visible
@@ -877,7 +2870,11 @@ impl eframe::App for TemplateApp {
                     let color = ui.style().visuals.text_color();
 
                     if let Some(diff) = diff {
-                        let str = format!("{}{diff}", if diff < 0 { "" } else { "+" });
+                        let str = format!(
+                            "{}{}",
+                            if diff < 0 { "" } else { "+" },
+                            format_time(diff as i64)
+                        );
                         let font = epaint::text::FontId::new(10.0, text::FontFamily::Monospace);
                         let galley = ui.fonts(|f| f.layout_no_wrap(str, font, color));
                         // let rect =
@@ -888,7 +2885,8 @@ impl eframe::App for TemplateApp {
                             color,
                         ));
                     }
-                    let galley = ui.fonts(|f| f.layout_no_wrap(used_i.to_string(), font, color));
+                    let galley =
+                        ui.fonts(|f| f.layout_no_wrap(format_time(used_i as i64), font, color));
                     let rect = Align2::LEFT_CENTER
                         .anchor_rect(Rect::from_min_size(p0 + vec2(4.0, 0.0), galley.size()));
                     ticks.push(Shape::galley(rect.min + vec2(4.0, 0.0), galley, color));
@@ -900,7 +2898,118 @@ impl eframe::App for TemplateApp {
 
         url_window.show(ctx, download);
         err_window.show(ctx);
+        warnings_window.show(ctx);
+        if let Some((format, rows, range)) =
+            export_window.show(ctx, wave_data, *final_time, &mut unread)
+        {
+            let rows: Vec<_> = rows
+                .into_iter()
+                .filter_map(|i| {
+                    let row = &wave_data[i];
+                    Some((row, &**row.signal.as_ref()?))
+                })
+                .collect();
+            // writing to memory can't fail
+            let mut bytes = vec![];
+            let file_name = match format {
+                ExportFormat::Vcd => {
+                    // derived and imported rows don't have the file's signal for their code so
+                    // they're given codes of their own
+                    let own_code = |row: &WaveRow| row.file_code().is_some();
+                    let mut used: std::collections::HashSet<_> = rows
+                        .iter()
+                        .filter(|(row, _)| own_code(row))
+                        .map(|(row, _)| row.var.var.code)
+                        .collect();
+                    let mut next = 0u64;
+                    let vars: Vec<_> = rows
+                        .iter()
+                        .map(|(row, _)| {
+                            let mut var = row.var.clone();
+                            if !own_code(row) {
+                                while !used.insert(::vcd::IdCode::from(next)) {
+                                    next += 1;
+                                }
+                                var.var.code = ::vcd::IdCode::from(next);
+                            }
+                            var
+                        })
+                        .collect();
+                    let vars: Vec<_> = vars.iter().zip(&rows).map(|(v, (_, s))| (v, *s)).collect();
+                    export::write_vcd(&mut bytes, &vars, *timescale, range).unwrap();
+                    "export.vcd"
+                }
+                ExportFormat::WaveDrom => {
+                    let lanes: Vec<_> = rows.iter().map(|(row, s)| (&*row.name, *s)).collect();
+                    wavedrom::write_wavejson(&mut bytes, &lanes, range).unwrap();
+                    "export.json"
+                }
+                ExportFormat::Csv { clock } => {
+                    let columns: Vec<_> = rows
+                        .iter()
+                        .map(|(row, s)| (&*row.name, *s, row.radix))
+                        .collect();
+                    let layout = match clock {
+                        Some((i, edge)) => export::CsvLayout::Sampled {
+                            // the window waits for the clock to be read
+                            clock: wave_data[i].signal.as_deref().unwrap(),
+                            edge,
+                        },
+                        None => export::CsvLayout::Changes,
+                    };
+                    export::write_csv(&mut bytes, &columns, range, layout).unwrap();
+                    "export.csv"
+                }
+            };
+            *a_save = Some(Box::pin(async move {
+                let handle = rfd::AsyncFileDialog::new()
+                    .set_file_name(file_name)
+                    .save_file()
+                    .await?;
+                Some(handle.write(&bytes).await)
+            }));
+        }
+        if let Some((name, msb, lsb)) = slice_window.show(ctx) {
+            // shown under the bus
+            if let Some(i) = wave_data.iter().position(|row| row.name == name) {
+                let row = wave_data[i].slice(msb, lsb);
+                wave_data.insert(i + 1, row);
+            }
+        }
+        if let Some((name, picked)) = concat_window.show(ctx, wave_data) {
+            let parts = picked.iter().map(|&i| wave_data[i].clone()).collect();
+            wave_data.push(WaveRow::concat(name, parts));
+        }
+        if let Some(row) = expr_window.show(ctx, wave_data) {
+            wave_data.push(row);
+        }
+        match import_window.show(ctx) {
+            Some(Ok(diagram)) => {
+                let mut rows = mk_wave_data(
+                    diagram
+                        .vars
+                        .iter()
+                        .map(|(var, signal)| (var, Some(Arc::clone(signal)))),
+                );
+                for row in &mut rows {
+                    row.source = RowSource::Imported;
+                }
+                if wave_data.is_empty() {
+                    *final_time = diagram.final_time.max(1);
+                    *x_scale = None;
+                } else {
+                    *final_time = (*final_time).max(diagram.final_time);
+                }
+                wave_data.extend(rows);
+            }
+            Some(Err(err)) => {
+                err_window.msg = format!("{} failed to import:\n{err}", import_window.name);
+                err_window.open = true;
+            }
+            None => (),
+        }
 
+        self.read_signals(unread);
         self.ui_file_drag_and_drop(ctx);
 
         if false {
@@ -914,20 +3023,46 @@ impl eframe::App for TemplateApp {
     }
 }
 
-fn mk_wave_data(sigs: Vec<(vcd::ScopedVar, vcd::Signal)>) -> Vec<(String, vcd::Signal)> {
-    sigs.into_iter()
-        .map(|(var, sig)| {
-            let mut name: String =
-                itertools::intersperse(var.scopes.iter().map(|x| x.1.as_str()), ".").collect();
-            if !name.is_empty() {
-                name.push('.');
+/// Round a gap between ticks up to 1, 2 or 5 times a power of 10 so the labels are round numbers.
+fn nice_gap(gap: usize) -> usize {
+    let mut pow = 1;
+    loop {
+        for m in [1, 2, 5] {
+            if m * pow >= gap {
+                return m * pow;
             }
-            name.push_str(&var.var.reference);
-            // let bools = sig.scalars().map(|(_, v)| v == vcd::Value::V1).collect();
-            // eprintln!("bools = {bools:?}");
-            (name, sig)
-        })
-        .collect()
+        }
+        pow *= 10;
+    }
+}
+
+fn mk_wave_data<'a>(
+    sigs: impl Iterator<Item = (&'a vcd::ScopedVar, Option<Arc<vcd::Signal>>)>,
+) -> Vec<WaveRow> {
+    sigs.map(|(var, sig)| {
+        let mut name: String =
+            itertools::intersperse(var.scopes.iter().map(|x| x.1.as_str()), ".").collect();
+        if !name.is_empty() {
+            name.push('.');
+        }
+        name.push_str(&var.var.reference);
+        // let bools = sig.scalars().map(|(_, v)| v == vcd::Value::V1).collect();
+        // eprintln!("bools = {bools:?}");
+        WaveRow {
+            name,
+            var: var.clone(),
+            signal: sig,
+            interpolation: wave::Interpolation::default(),
+            radix: vcd::Radix::default(),
+            source: RowSource::File,
+            derived: None,
+            parts: vec![],
+            inputs: vec![],
+            error: None,
+            id: next_row_id(),
+        }
+    })
+    .collect()
 }
 
 impl TemplateApp {
@@ -968,21 +3103,68 @@ impl TemplateApp {
             self.dropped_files = ctx.input(|i| i.raw.dropped_files.clone());
         }
 
-        // Show dropped files (if any):
+        // Load the first dropped file (if any):
         if !self.dropped_files.is_empty() {
-            if let Some(path) = &self.dropped_files[0].path {
-                let mut file = std::fs::File::open(path).unwrap();
-                let mut buf_file = std::io::BufReader::new(&mut file);
-                let (sigs, time) = vcd::read_clocked_vcd(&mut buf_file).unwrap();
-                self.final_time = time;
-                self.wave_data = mk_wave_data(sigs);
-            } else if let Some(bytes) = &self.dropped_files[0].bytes {
-                let mut cursor = std::io::Cursor::new(&bytes);
-                let (sigs, time) = vcd::read_clocked_vcd(&mut cursor).unwrap();
-                self.final_time = time;
-                self.wave_data = mk_wave_data(sigs);
+            let file = self.dropped_files.swap_remove(0);
+            #[cfg(not(target_arch = "wasm32"))]
+            if let Some(path) = file.path {
+                self.load_path(path);
+            }
+            // web drops only have the bytes
+            if let Some(bytes) = file.bytes {
+                self.start_loading(crate::load::Loading::from_bytes(file.name, bytes.to_vec()));
             }
         }
         self.dropped_files.clear();
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_group_bus_bits() {
+        let vcd = "$scope module top $end
+$var wire 1 ! data [1] $end
+$var wire 1 \" clk $end
+$var wire 1 # data [0] $end
+$var wire 1 $ addr[0] $end
+$var wire 1 % addr[2] $end
+$var wire 1 & mask[3] $end
+$var wire 1 ' mask[1] $end
+$var wire 1 ( mask[1] $end
+$var wire 1 ) mask[0] $end
+$upscope $end
+$enddefinitions $end
+#0
+0!
+1#
+0\"
+#5
+1!
+";
+        let wave = vcd::read_clocked_vcd(&mut vcd.as_bytes()).unwrap();
+        let mut rows = mk_wave_data(wave.vars.iter().map(|(v, s)| (v, Some(Arc::clone(s)))));
+        // addr[1] is missing, mask[2] is missing and mask[1] is there twice
+        assert_eq!(bus_groups(&rows).len(), 1);
+        group_bus_bits(&mut rows);
+        let names: Vec<_> = rows.iter().map(|row| &*row.name).collect();
+        assert_eq!(
+            names,
+            [
+                "top.data[1:0]",
+                "top.clk",
+                "top.addr[0]",
+                "top.addr[2]",
+                "top.mask[3]",
+                "top.mask[1]",
+                "top.mask[1]",
+                "top.mask[0]"
+            ]
+        );
+        let data = rows[0].signal.as_ref().unwrap();
+        assert_eq!(data.value_at(0), Some(vec![vcd::Value::V0, vcd::Value::V1]));
+        assert_eq!(data.value_at(5), Some(vec![vcd::Value::V1, vcd::Value::V1]));
+    }
+}
@@ -0,0 +1,13 @@
+//! The web worker that loads waveforms off the main thread, see `waveview::load::Loading`. Trunk
+//! builds it alongside the app (see `index.html`), it does nothing natively.
+
+#[cfg(target_arch = "wasm32")]
+fn main() {
+    console_error_panic_hook::set_once();
+    waveview::load::run_worker();
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn main() {
+    eprintln!("the worker is only used by the web build");
+}
@@ -0,0 +1,369 @@
+use crate::vcd::{Radix, ScopeType, ScopedVar, Signal, Timescale, Value};
+use indexmap::IndexMap;
+use std::collections::HashSet;
+use std::io::{self, Write};
+use std::ops::RangeInclusive;
+
+// Writing the signals being looked at back out, i.e. to hand a small reproducer to someone else.
+
+/// The vars declared directly in a scope and its child scopes, in the order they first appear.
+#[derive(Default)]
+struct Scope<'a> {
+    vars: Vec<&'a ScopedVar>,
+    children: IndexMap<(ScopeType, &'a str), Scope<'a>>,
+}
+
+impl<'a> Scope<'a> {
+    fn add(&mut self, var: &'a ScopedVar) {
+        let mut scope = self;
+        for (scope_type, name) in &var.scopes {
+            scope = scope.children.entry((*scope_type, name)).or_default();
+        }
+        scope.vars.push(var);
+    }
+
+    fn write(&self, w: &mut impl Write) -> io::Result<()> {
+        for var in &self.vars {
+            let var = &var.var;
+            write!(
+                w,
+                "$var {} {} {} {}",
+                var.var_type, var.size, var.code, var.reference
+            )?;
+            if let Some(index) = &var.index {
+                write!(w, " {index}")?;
+            }
+            writeln!(w, " $end")?;
+        }
+        for ((scope_type, name), child) in &self.children {
+            writeln!(w, "$scope {scope_type} {name} $end")?;
+            child.write(w)?;
+            writeln!(w, "$upscope $end")?;
+        }
+        Ok(())
+    }
+}
+
+/// Write the value of `signal` at `time` as a VCD value change, nothing if it has no value yet.
+fn write_change(
+    w: &mut impl Write,
+    code: ::vcd::IdCode,
+    signal: &Signal,
+    time: u64,
+) -> io::Result<()> {
+    if signal.is_real() {
+        if let Some(v) = signal.real_at(time) {
+            writeln!(w, "r{v} {code}")?;
+        }
+    } else if signal.is_string() {
+        if let Some(s) = signal.string_at(time) {
+            writeln!(w, "s{s} {code}")?;
+        }
+    } else if let Some(bits) = signal.value_at(time) {
+        let bits: String = bits.iter().map(|v| v.to_char()).collect();
+        if bits.len() == 1 {
+            writeln!(w, "{bits}{code}")?;
+        } else {
+            writeln!(w, "b{bits} {code}")?;
+        }
+    }
+    Ok(())
+}
+
+/// Write `vars` over the times in `range` as a VCD file that loads again with the same hierarchy,
+/// var types, id codes and timescale. Vars sharing a code share a signal so it's only written once.
+///
+/// Times are kept as they were rather than moved to start at 0. The values at the start of the
+/// range are given in a `$dumpvars` and the file ends with a timestamp at the end of the range.
+pub fn write_vcd(
+    w: &mut impl Write,
+    vars: &[(&ScopedVar, &Signal)],
+    timescale: Option<Timescale>,
+    range: RangeInclusive<u64>,
+) -> io::Result<()> {
+    let mut top = Scope::default();
+    let mut signals: IndexMap<::vcd::IdCode, &Signal> = IndexMap::new();
+    // the same var can be shown in more than one row
+    let mut declared = HashSet::new();
+    for &(var, signal) in vars {
+        if declared.insert((&var.scopes, &var.var.reference, var.var.code)) {
+            top.add(var);
+            signals.entry(var.var.code).or_insert(signal);
+        }
+    }
+
+    writeln!(w, "$version waveview {} $end", env!("CARGO_PKG_VERSION"))?;
+    if let Some(timescale) = timescale {
+        writeln!(w, "$timescale {timescale} $end")?;
+    }
+    top.write(w)?;
+    writeln!(w, "$enddefinitions $end")?;
+
+    let (start, end) = (*range.start(), *range.end());
+    writeln!(w, "#{start}")?;
+    writeln!(w, "$dumpvars")?;
+    for (&code, signal) in &signals {
+        write_change(w, code, signal, start)?;
+    }
+    writeln!(w, "$end")?;
+
+    // merge the changes of every signal in time order, keeping the order of the vars at each time
+    let mut changes = vec![];
+    for (i, signal) in signals.values().enumerate() {
+        let mut time = start;
+        while let Some(next) = signal.next_change(time).filter(|&t| t <= end) {
+            changes.push((next, i));
+            time = next;
+        }
+    }
+    changes.sort_by_key(|&(time, _)| time);
+    let mut time = start;
+    for (t, i) in changes {
+        if t != time {
+            writeln!(w, "#{t}")?;
+            time = t;
+        }
+        let (&code, signal) = signals.get_index(i).unwrap();
+        write_change(w, code, signal, t)?;
+    }
+    if end > time {
+        writeln!(w, "#{end}")?;
+    }
+    Ok(())
+}
+
+/// Which clock edges a sampled CSV export has a row for.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Edge {
+    #[default]
+    Rising,
+    Falling,
+    Both,
+}
+
+impl Edge {
+    pub const ALL: [Edge; 3] = [Edge::Rising, Edge::Falling, Edge::Both];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Edge::Rising => "Rising",
+            Edge::Falling => "Falling",
+            Edge::Both => "Both",
+        }
+    }
+
+    fn matches(self, from: Option<Value>, to: Value) -> bool {
+        let rising = to == Value::V1 && from != Some(Value::V1);
+        let falling = to == Value::V0 && from != Some(Value::V0);
+        match self {
+            Edge::Rising => rising,
+            Edge::Falling => falling,
+            Edge::Both => rising || falling,
+        }
+    }
+}
+
+/// How the rows of a CSV export are laid out.
+#[derive(Clone, Copy)]
+pub enum CsvLayout<'a> {
+    /// A `time,signal,value` row for every value change, starting with the values at the start of
+    /// the range.
+    Changes,
+    /// A time column and a column for each signal, with a row for every `edge` of `clock`. The
+    /// values are the ones going into the edge, what a flop on that clock would capture.
+    Sampled { clock: &'a Signal, edge: Edge },
+}
+
+/// Quote a CSV field if it needs it.
+fn csv_field(s: &str) -> std::borrow::Cow<'_, str> {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\"")).into()
+    } else {
+        s.into()
+    }
+}
+
+/// The value of `signal` at `time` as text, a label if it has one.
+fn csv_value(signal: &Signal, radix: Radix, time: u64) -> Option<String> {
+    if signal.is_real() {
+        signal.real_at(time).map(|v| v.to_string())
+    } else if signal.is_string() {
+        signal.string_at(time).map(str::to_owned)
+    } else {
+        let bits = signal.value_at(time)?;
+        Some(
+            signal
+                .label(&bits)
+                .map_or_else(|| radix.format(&bits), str::to_owned),
+        )
+    }
+}
+
+/// Write `columns` of (name, signal, radix) over the times in `range` as CSV, with a header row.
+/// Times are in ticks of the file's timescale. Values a signal doesn't have yet are left empty.
+pub fn write_csv(
+    w: &mut impl Write,
+    columns: &[(&str, &Signal, Radix)],
+    range: RangeInclusive<u64>,
+    layout: CsvLayout<'_>,
+) -> io::Result<()> {
+    let (start, end) = (*range.start(), *range.end());
+    match layout {
+        CsvLayout::Changes => {
+            writeln!(w, "time,signal,value")?;
+            let mut changes: Vec<(u64, usize)> = (0..columns.len()).map(|i| (start, i)).collect();
+            for (i, (_, signal, _)) in columns.iter().enumerate() {
+                let after_start = start.saturating_add(1)..end.saturating_add(1);
+                changes.extend(signal.change_times(after_start).iter().map(|&t| (t, i)));
+            }
+            changes.sort_by_key(|&(time, _)| time);
+            for (t, i) in changes {
+                let (name, signal, radix) = columns[i];
+                if let Some(value) = csv_value(signal, radix, t) {
+                    writeln!(w, "{t},{},{}", csv_field(name), csv_field(&value))?;
+                }
+            }
+        }
+        CsvLayout::Sampled { clock, edge } => {
+            write!(w, "time")?;
+            for (name, _, _) in columns {
+                write!(w, ",{}", csv_field(name))?;
+            }
+            writeln!(w)?;
+            for &t in clock.change_times(start..end.saturating_add(1)) {
+                let from = clock.prev_change(t).and_then(|p| clock.bit_at(p));
+                if !clock.bit_at(t).is_some_and(|to| edge.matches(from, to)) {
+                    continue;
+                }
+                write!(w, "{t}")?;
+                for &(_, signal, radix) in columns {
+                    let value = signal
+                        .prev_change(t)
+                        .and_then(|p| csv_value(signal, radix, p))
+                        .unwrap_or_default();
+                    write!(w, ",{}", csv_field(&value))?;
+                }
+                writeln!(w)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vcd::{read_clocked_vcd, Value};
+
+    #[test]
+    fn test_write_vcd() {
+        let vcd = "$timescale 10ps $end
+$scope module top $end
+$var wire 1 ! clk $end
+$scope module sub $end
+$var logic 4 \" data [3:0] $end
+$var real 64 # volts $end
+$upscope $end
+$var wire 1 ! clk_alias $end
+$upscope $end
+$enddefinitions $end
+#0
+$dumpvars
+0!
+b0000 \"
+r0.5 #
+$end
+#5
+1!
+b1010 \"
+#10
+0!
+r1.25 #
+#15
+1!
+b0110 \"
+#20
+0!
+";
+        let wave = read_clocked_vcd(&mut vcd.as_bytes()).unwrap();
+        let vars: Vec<_> = wave.vars.iter().map(|(v, s)| (v, &**s)).collect();
+        let mut out = vec![];
+        write_vcd(&mut out, &vars[1..], wave.timescale, 7..=15).unwrap();
+
+        let window = read_clocked_vcd(&mut &out[..]).unwrap();
+        assert_eq!(window.timescale, wave.timescale);
+        assert_eq!(window.final_time, 15);
+        let names: Vec<_> = window
+            .vars
+            .iter()
+            .map(|(v, _)| (v.scopes.len(), v.var.reference.as_str()))
+            .collect();
+        assert_eq!(names, [(1, "clk_alias"), (2, "data"), (2, "volts")]);
+        assert!(matches!(
+            window.vars[1].0.var.index,
+            Some(::vcd::ReferenceIndex::Range(3, 0))
+        ));
+
+        let data = &window.vars[1].1;
+        assert_eq!(data.value_at(7).unwrap()[0], Value::V1);
+        assert_eq!(data.prev_change(15), Some(7));
+        assert_eq!(data.value_at(15).unwrap()[1], Value::V1);
+        let volts = &window.vars[2].1;
+        assert_eq!(volts.real_at(7), Some(0.5));
+        assert_eq!(volts.real_at(10), Some(1.25));
+        let clk = &window.vars[0].1;
+        assert_eq!(clk.count_changes(0..16), 3);
+    }
+
+    #[test]
+    fn test_write_csv() {
+        let vcd = "$scope module top $end
+$var wire 1 ! clk $end
+$var wire 8 \" count $end
+$var string 1 # state $end
+$upscope $end
+$enddefinitions $end
+#0
+0!
+b0 \"
+sidle #
+#5
+1!
+#10
+0!
+b11111111 \"
+sa,b #
+#15
+1!
+b00000010 \"
+#20
+0!
+";
+        let wave = read_clocked_vcd(&mut vcd.as_bytes()).unwrap();
+        let clk = &wave.vars[0].1;
+        let count = &wave.vars[1].1;
+        let state = &wave.vars[2].1;
+        let columns = [
+            ("count", &**count, Radix::Signed),
+            ("state", &**state, Radix::Hex),
+        ];
+
+        let mut out = vec![];
+        write_csv(&mut out, &columns, 5..=15, CsvLayout::Changes).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "time,signal,value\n5,count,0\n5,state,idle\n10,count,-1\n10,state,\"a,b\"\n15,count,2\n"
+        );
+
+        let mut out = vec![];
+        let layout = CsvLayout::Sampled {
+            clock: clk,
+            edge: Edge::Rising,
+        };
+        write_csv(&mut out, &columns, 0..=20, layout).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "time,count,state\n5,0,idle\n15,-1,\"a,b\"\n"
+        );
+    }
+}
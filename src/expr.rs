@@ -0,0 +1,664 @@
+use crate::vcd::{Signal, Value};
+use std::io;
+
+// Signals made from other signals with Verilog-like expressions, i.e. `valid && ready`,
+// `count == 8'hff` or `addr[15:0] + 4`. Values are 4-state, x and z propagate the way they would
+// in simulation.
+
+fn expr_error(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg.into())
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum UnaryOp {
+    Not,
+    Invert,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum BinaryOp {
+    Add,
+    Sub,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+    And,
+    Xor,
+    Or,
+    LogicalAnd,
+    LogicalOr,
+}
+
+// lowest to highest precedence, the same as verilog
+const PRECEDENCE: &[&[(&str, BinaryOp)]] = &[
+    &[("||", BinaryOp::LogicalOr)],
+    &[("&&", BinaryOp::LogicalAnd)],
+    &[("|", BinaryOp::Or)],
+    &[("^", BinaryOp::Xor)],
+    &[("&", BinaryOp::And)],
+    &[("==", BinaryOp::Eq), ("!=", BinaryOp::Ne)],
+    &[
+        ("<=", BinaryOp::Le),
+        ("<", BinaryOp::Lt),
+        (">=", BinaryOp::Ge),
+        (">", BinaryOp::Gt),
+    ],
+    &[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+];
+
+/// A parsed expression.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Expr {
+    node: Node,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum Node {
+    Signal(String),
+    /// Bits, most significant first.
+    Const(Vec<Value>),
+    Unary(UnaryOp, Box<Node>),
+    Binary(BinaryOp, Box<Node>, Box<Node>),
+    Cond(Box<Node>, Box<Node>, Box<Node>),
+    /// Bits `msb` down to `lsb`, counting from 0 for the least significant.
+    Slice(Box<Node>, usize, usize),
+    /// Most significant first.
+    Concat(Vec<Node>),
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '$')
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    fn skip_space(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    /// Take `token` if it's next.
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_space();
+        // `|` and `&` aren't the start of `||` and `&&`, `<` isn't the start of `<=`
+        let longer = |next: char| {
+            token.len() == 1
+                && ((matches!(token, "|" | "&") && next == token.as_bytes()[0] as char)
+                    || (matches!(token, "<" | ">" | "!" | "=") && next == '='))
+        };
+        match self.rest().strip_prefix(token) {
+            Some(after) if !after.starts_with(longer) => {
+                self.pos += token.len();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> io::Result<()> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected `{token}`")))
+        }
+    }
+
+    fn error(&self, msg: &str) -> io::Error {
+        match self.rest().chars().next() {
+            Some(_) => expr_error(format!("{msg} at column {}", self.pos + 1)),
+            None => expr_error(format!("{msg} at the end")),
+        }
+    }
+
+    fn cond(&mut self) -> io::Result<Node> {
+        let cond = self.binary(0)?;
+        if !self.eat("?") {
+            return Ok(cond);
+        }
+        let then = self.cond()?;
+        self.expect(":")?;
+        let otherwise = self.cond()?;
+        Ok(Node::Cond(
+            Box::new(cond),
+            Box::new(then),
+            Box::new(otherwise),
+        ))
+    }
+
+    fn binary(&mut self, level: usize) -> io::Result<Node> {
+        let Some(ops) = PRECEDENCE.get(level) else {
+            return self.unary();
+        };
+        let mut lhs = self.binary(level + 1)?;
+        'next: loop {
+            for &(token, op) in *ops {
+                if self.eat(token) {
+                    let rhs = self.binary(level + 1)?;
+                    lhs = Node::Binary(op, Box::new(lhs), Box::new(rhs));
+                    continue 'next;
+                }
+            }
+            return Ok(lhs);
+        }
+    }
+
+    fn unary(&mut self) -> io::Result<Node> {
+        if self.eat("!") {
+            Ok(Node::Unary(UnaryOp::Not, Box::new(self.unary()?)))
+        } else if self.eat("~") {
+            Ok(Node::Unary(UnaryOp::Invert, Box::new(self.unary()?)))
+        } else {
+            self.postfix()
+        }
+    }
+
+    fn postfix(&mut self) -> io::Result<Node> {
+        let mut node = self.primary()?;
+        while self.eat("[") {
+            let msb = self.index()?;
+            let lsb = if self.eat(":") { self.index()? } else { msb };
+            self.expect("]")?;
+            if lsb > msb {
+                return Err(self.error("slices go from the most significant bit down"));
+            }
+            node = Node::Slice(Box::new(node), msb, lsb);
+        }
+        Ok(node)
+    }
+
+    fn index(&mut self) -> io::Result<usize> {
+        self.skip_space();
+        let digits = self.rest().len()
+            - self
+                .rest()
+                .trim_start_matches(|c: char| c.is_ascii_digit())
+                .len();
+        let index = self.rest()[..digits]
+            .parse()
+            .map_err(|_| self.error("expected a bit number"))?;
+        self.pos += digits;
+        Ok(index)
+    }
+
+    fn primary(&mut self) -> io::Result<Node> {
+        if self.eat("(") {
+            let node = self.cond()?;
+            self.expect(")")?;
+            return Ok(node);
+        }
+        if self.eat("{") {
+            let mut parts = vec![self.cond()?];
+            while self.eat(",") {
+                parts.push(self.cond()?);
+            }
+            self.expect("}")?;
+            return Ok(Node::Concat(parts));
+        }
+        self.skip_space();
+        let rest = self.rest();
+        match rest.chars().next() {
+            Some(c) if c.is_ascii_digit() || c == '\'' => self.number(),
+            Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+                let len = rest.len() - rest.trim_start_matches(is_name_char).len();
+                self.pos += len;
+                Ok(Node::Signal(rest[..len].to_owned()))
+            }
+            _ => Err(self.error("expected a signal or number")),
+        }
+    }
+
+    /// A verilog number like `12`, `8'hff`, `4'b10x1` or `'d3`. Unsized numbers are 32 bits.
+    fn number(&mut self) -> io::Result<Node> {
+        let rest = self.rest();
+        let len = rest.len()
+            - rest
+                .trim_start_matches(|c: char| c.is_ascii_alphanumeric() || matches!(c, '_' | '\''))
+                .len();
+        let text: String = rest[..len].chars().filter(|&c| c != '_').collect();
+        let (size, base, digits) = match text.split_once('\'') {
+            Some((size, based)) => {
+                let size = match size {
+                    "" => None,
+                    size => Some(size.parse().map_err(|_| self.error("bad number size"))?),
+                };
+                let mut chars = based.chars();
+                let base = chars.next().map(|c| c.to_ascii_lowercase());
+                (size, base, chars.as_str())
+            }
+            None => (None, Some('d'), &*text),
+        };
+        let bits = match base {
+            Some('b') => based_bits(digits, 1),
+            Some('o') => based_bits(digits, 3),
+            Some('h') => based_bits(digits, 4),
+            Some('d') => decimal_bits(digits),
+            _ => None,
+        }
+        .ok_or_else(|| self.error("bad number"))?;
+        self.pos += len;
+        let size = size.unwrap_or(bits.len().max(32));
+        if size == 0 {
+            return Err(self.error("numbers need at least one bit"));
+        }
+        Ok(Node::Const(resize(bits, size)))
+    }
+}
+
+/// A number on its own, like the constants in an expression. Gives its bits, most significant
+/// first.
+pub fn parse_number(text: &str) -> io::Result<Vec<Value>> {
+    let mut parser = Parser { text, pos: 0 };
+    parser.skip_space();
+    let node = parser.number()?;
+    parser.skip_space();
+    match node {
+        Node::Const(bits) if parser.rest().is_empty() => Ok(bits),
+        _ => Err(parser.error("unexpected text")),
+    }
+}
+
+/// The bits of binary, octal or hex digits, x and z filling a whole digit.
+fn based_bits(digits: &str, bits_per_digit: u32) -> Option<Vec<Value>> {
+    if digits.is_empty() {
+        return None;
+    }
+    let mut bits = vec![];
+    for c in digits.chars() {
+        let value = match c.to_ascii_lowercase() {
+            'x' => Some(Value::X),
+            'z' => Some(Value::Z),
+            _ => None,
+        };
+        let n = match value {
+            Some(_) => 0,
+            None => c.to_digit(1 << bits_per_digit)?,
+        };
+        for i in (0..bits_per_digit).rev() {
+            bits.push(value.unwrap_or(if n >> i & 1 == 1 {
+                Value::V1
+            } else {
+                Value::V0
+            }));
+        }
+    }
+    Some(bits)
+}
+
+fn decimal_bits(digits: &str) -> Option<Vec<Value>> {
+    match digits.to_ascii_lowercase().as_str() {
+        "x" => return Some(vec![Value::X]),
+        "z" => return Some(vec![Value::Z]),
+        _ => (),
+    }
+    let n: u128 = digits.parse().ok()?;
+    let width = (128 - n.leading_zeros()).max(1);
+    Some(
+        (0..width)
+            .rev()
+            .map(|i| {
+                if n >> i & 1 == 1 {
+                    Value::V1
+                } else {
+                    Value::V0
+                }
+            })
+            .collect(),
+    )
+}
+
+/// Truncate or extend `bits` to `width`, extending with zeros or the top bit if it's x or z like
+/// verilog does.
+pub(crate) fn resize(mut bits: Vec<Value>, width: usize) -> Vec<Value> {
+    if bits.len() >= width {
+        return bits.split_off(bits.len() - width);
+    }
+    let fill = match bits.first() {
+        Some(&v @ (Value::X | Value::Z)) => v,
+        _ => Value::V0,
+    };
+    let mut resized = vec![fill; width - bits.len()];
+    resized.extend(bits);
+    resized
+}
+
+fn known(v: Value) -> Option<bool> {
+    match v {
+        Value::V0 => Some(false),
+        Value::V1 => Some(true),
+        _ => None,
+    }
+}
+
+fn from_bool(b: Option<bool>) -> Value {
+    match b {
+        Some(true) => Value::V1,
+        Some(false) => Value::V0,
+        None => Value::X,
+    }
+}
+
+/// Whether a value is true, i.e. has any bit set. x when it has no bits set but some unknown.
+fn truth(bits: &[Value]) -> Option<bool> {
+    if bits.contains(&Value::V1) {
+        Some(true)
+    } else if bits.iter().all(|&v| v == Value::V0) {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+fn and(a: Option<bool>, b: Option<bool>) -> Option<bool> {
+    match (a, b) {
+        (Some(false), _) | (_, Some(false)) => Some(false),
+        (Some(true), Some(true)) => Some(true),
+        _ => None,
+    }
+}
+
+fn or(a: Option<bool>, b: Option<bool>) -> Option<bool> {
+    match (a, b) {
+        (Some(true), _) | (_, Some(true)) => Some(true),
+        (Some(false), Some(false)) => Some(false),
+        _ => None,
+    }
+}
+
+/// The value of unsigned bits, `None` if any are unknown.
+fn to_bools(bits: &[Value]) -> Option<Vec<bool>> {
+    bits.iter().map(|&v| known(v)).collect()
+}
+
+/// `a + b` or `a - b` of the same width, wrapping around.
+fn add(a: &[bool], b: &[bool], subtract: bool) -> Vec<Value> {
+    // subtracting adds the two's complement
+    let mut carry = subtract;
+    let mut sum = vec![Value::V0; a.len()];
+    for i in (0..a.len()).rev() {
+        let b = b[i] ^ subtract;
+        sum[i] = from_bool(Some(a[i] ^ b ^ carry));
+        carry = (a[i] && b) || (carry && (a[i] ^ b));
+    }
+    sum
+}
+
+impl Node {
+    /// The width of the result, checking the signals exist and slices are in range.
+    fn width(&self, width_of: &impl Fn(&str) -> Option<usize>) -> io::Result<usize> {
+        Ok(match self {
+            Node::Signal(name) => {
+                width_of(name).ok_or_else(|| expr_error(format!("no bit signal `{name}`")))?
+            }
+            Node::Const(bits) => bits.len(),
+            Node::Unary(UnaryOp::Not, a) => {
+                a.width(width_of)?;
+                1
+            }
+            Node::Unary(UnaryOp::Invert, a) => a.width(width_of)?,
+            Node::Binary(op, a, b) => {
+                let (a, b) = (a.width(width_of)?, b.width(width_of)?);
+                match op {
+                    BinaryOp::Add
+                    | BinaryOp::Sub
+                    | BinaryOp::And
+                    | BinaryOp::Xor
+                    | BinaryOp::Or => a.max(b),
+                    _ => 1,
+                }
+            }
+            Node::Cond(cond, a, b) => {
+                cond.width(width_of)?;
+                a.width(width_of)?.max(b.width(width_of)?)
+            }
+            Node::Slice(a, msb, lsb) => {
+                let width = a.width(width_of)?;
+                if *msb >= width {
+                    return Err(expr_error(format!("bit {msb} of a {width} bit value")));
+                }
+                msb - lsb + 1
+            }
+            Node::Concat(parts) => {
+                let mut width = 0;
+                for part in parts {
+                    width += part.width(width_of)?;
+                }
+                width
+            }
+        })
+    }
+
+    /// The value with the signals' values given by `value_of`, which has to give bits of the
+    /// width checked by [`Node::width`].
+    fn eval(&self, value_of: &impl Fn(&str) -> Vec<Value>) -> Vec<Value> {
+        match self {
+            Node::Signal(name) => value_of(name),
+            Node::Const(bits) => bits.clone(),
+            Node::Unary(UnaryOp::Not, a) => {
+                vec![from_bool(truth(&a.eval(value_of)).map(|t| !t))]
+            }
+            Node::Unary(UnaryOp::Invert, a) => a
+                .eval(value_of)
+                .into_iter()
+                .map(|v| from_bool(known(v).map(|b| !b)))
+                .collect(),
+            Node::Binary(op, a, b) => {
+                let (a, b) = (a.eval(value_of), b.eval(value_of));
+                let width = a.len().max(b.len());
+                let (a, b) = (resize(a, width), resize(b, width));
+                let bitwise = |f: fn(Option<bool>, Option<bool>) -> Option<bool>| {
+                    a.iter()
+                        .zip(&b)
+                        .map(|(&a, &b)| from_bool(f(known(a), known(b))))
+                        .collect()
+                };
+                match op {
+                    BinaryOp::And => bitwise(and),
+                    BinaryOp::Or => bitwise(or),
+                    BinaryOp::Xor => bitwise(|a, b| Some(a? ^ b?)),
+                    BinaryOp::LogicalAnd => vec![from_bool(and(truth(&a), truth(&b)))],
+                    BinaryOp::LogicalOr => vec![from_bool(or(truth(&a), truth(&b)))],
+                    BinaryOp::Add | BinaryOp::Sub => match (to_bools(&a), to_bools(&b)) {
+                        (Some(a), Some(b)) => add(&a, &b, *op == BinaryOp::Sub),
+                        _ => vec![Value::X; width],
+                    },
+                    // bools compare the same as unsigned numbers of the same width
+                    _ => {
+                        let ord = to_bools(&a).zip(to_bools(&b)).map(|(a, b)| a.cmp(&b));
+                        vec![from_bool(ord.map(|ord| match op {
+                            BinaryOp::Lt => ord.is_lt(),
+                            BinaryOp::Le => ord.is_le(),
+                            BinaryOp::Gt => ord.is_gt(),
+                            BinaryOp::Ge => ord.is_ge(),
+                            BinaryOp::Eq => ord.is_eq(),
+                            _ => ord.is_ne(),
+                        }))]
+                    }
+                }
+            }
+            Node::Cond(cond, a, b) => {
+                let (a, b) = (a.eval(value_of), b.eval(value_of));
+                let width = a.len().max(b.len());
+                let (a, b) = (resize(a, width), resize(b, width));
+                match truth(&cond.eval(value_of)) {
+                    Some(true) => a,
+                    Some(false) => b,
+                    // bits that are the same either way are still known
+                    None => a
+                        .iter()
+                        .zip(&b)
+                        .map(|(&a, &b)| {
+                            if a == b && known(a).is_some() {
+                                a
+                            } else {
+                                Value::X
+                            }
+                        })
+                        .collect(),
+                }
+            }
+            Node::Slice(a, msb, lsb) => {
+                let bits = a.eval(value_of);
+                let n = bits.len();
+                bits[n - 1 - msb..n - lsb].to_vec()
+            }
+            Node::Concat(parts) => parts.iter().flat_map(|p| p.eval(value_of)).collect(),
+        }
+    }
+
+    fn names<'a>(&'a self, names: &mut Vec<&'a str>) {
+        match self {
+            Node::Signal(name) => {
+                if !names.contains(&&**name) {
+                    names.push(name);
+                }
+            }
+            Node::Const(_) => (),
+            Node::Unary(_, a) | Node::Slice(a, _, _) => a.names(names),
+            Node::Binary(_, a, b) => {
+                a.names(names);
+                b.names(names);
+            }
+            Node::Cond(cond, a, b) => {
+                cond.names(names);
+                a.names(names);
+                b.names(names);
+            }
+            Node::Concat(parts) => parts.iter().for_each(|p| p.names(names)),
+        }
+    }
+}
+
+impl Expr {
+    pub fn parse(text: &str) -> io::Result<Expr> {
+        let mut parser = Parser { text, pos: 0 };
+        let node = parser.cond()?;
+        parser.skip_space();
+        if !parser.rest().is_empty() {
+            return Err(parser.error("unexpected text"));
+        }
+        Ok(Expr { node })
+    }
+
+    /// The names of the signals used, in the order they first appear.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names = vec![];
+        self.node.names(&mut names);
+        names
+    }
+
+    /// Evaluate the expression everywhere any of its signals change, giving a bit vector signal.
+    /// `signals` has the signal for each name. Signals are x before their first change.
+    pub fn eval(&self, signals: &[(&str, &Signal)]) -> io::Result<Signal> {
+        let find = |name: &str| signals.iter().find(|(n, _)| *n == name).map(|(_, s)| *s);
+        let width_of = |name: &str| {
+            find(name)
+                .filter(|s| !s.is_real() && !s.is_string())
+                .map(|s| s.width())
+        };
+        let width = self.node.width(&width_of)?;
+
+        let mut signal = Signal::new(width);
+        let mut times: Vec<u64> = signals
+            .iter()
+            .flat_map(|(_, s)| s.change_times(0..u64::MAX))
+            .copied()
+            .collect();
+        times.sort_unstable();
+        times.dedup();
+        // constants still have a value
+        if times.first() != Some(&0) {
+            times.insert(0, 0);
+        }
+        for time in times {
+            let value = self.node.eval(&|name| {
+                let s = find(name).unwrap();
+                s.value_at(time)
+                    .unwrap_or_else(|| vec![Value::X; s.width()])
+            });
+            if !signal.ends_with(&value) {
+                signal.insert(time, value);
+            }
+        }
+        let end = signals
+            .iter()
+            .map(|(_, s)| s.final_time())
+            .max()
+            .unwrap_or(0);
+        signal.extend_to(end);
+        Ok(signal)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vcd::{bits, read_clocked_vcd};
+
+    #[test]
+    fn test_expr() {
+        let vcd = "$scope module top $end
+$var wire 1 ! valid $end
+$var wire 1 \" ready $end
+$var wire 8 # count $end
+$upscope $end
+$enddefinitions $end
+#0
+0!
+x\"
+b11111110 #
+#5
+1!
+#10
+1\"
+b11111111 #
+#15
+0\"
+bxxxxxxx1 #
+";
+        let wave = read_clocked_vcd(&mut vcd.as_bytes()).unwrap();
+        let signals: Vec<_> = ["valid", "ready", "count"]
+            .into_iter()
+            .zip(wave.vars.iter().map(|(_, s)| &**s))
+            .collect();
+        let eval = |text: &str| Expr::parse(text).unwrap().eval(&signals).unwrap();
+
+        let both = eval("valid && ready");
+        assert_eq!(both.value_at(0), Some(bits("0")));
+        assert_eq!(both.value_at(5), Some(bits("x")));
+        assert_eq!(both.value_at(10), Some(bits("1")));
+        assert_eq!(both.count_changes(0..20), 4);
+
+        let full = eval("count == 8'hff");
+        assert_eq!(full.value_at(0), Some(bits("0")));
+        assert_eq!(full.value_at(10), Some(bits("1")));
+        assert_eq!(full.value_at(15), Some(bits("x")));
+
+        let sum = eval("count[3:0] + 4'd3");
+        assert_eq!(sum.value_at(0), Some(bits("0001")));
+        assert_eq!(sum.value_at(15), Some(bits("xxxx")));
+        assert_eq!(eval("{valid, count[0]}").value_at(10), Some(bits("11")));
+        assert_eq!(eval("ready ? 2'b10 : 2'b11").value_at(0), Some(bits("1x")));
+        assert_eq!(eval("~count & 8'b1x").value_at(0), Some(bits("0000000x")));
+
+        let err = |text: &str| {
+            Expr::parse(text)
+                .and_then(|e| e.eval(&signals))
+                .unwrap_err()
+        };
+        assert!(err("valid &&").to_string().contains("at the end"));
+        assert!(err("count[8]").to_string().contains("bit 8"));
+        assert!(err("missing + 1").to_string().contains("`missing`"));
+        assert_eq!(Expr::parse("a.b && (c | 1)").unwrap().names(), ["a.b", "c"]);
+    }
+}
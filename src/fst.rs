@@ -0,0 +1,274 @@
+use crate::vcd::{
+    add_warning, keep_low_bits, ParseError, ScopeType, ScopedVar, Signal, Timescale, Value, Var,
+    VarType, Waveform,
+};
+use fst_reader::{
+    FstFilter, FstHierarchyEntry, FstReader, FstScopeType, FstSignalHandle, FstSignalValue,
+    FstVarType,
+};
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::sync::Arc;
+
+fn scope_type(tpe: FstScopeType) -> ScopeType {
+    match tpe {
+        FstScopeType::Module => ScopeType::Module,
+        FstScopeType::Task => ScopeType::Task,
+        FstScopeType::Function => ScopeType::Function,
+        FstScopeType::Begin => ScopeType::Begin,
+        FstScopeType::Fork => ScopeType::Fork,
+        FstScopeType::Generate => ScopeType::Generate,
+        FstScopeType::Struct => ScopeType::Struct,
+        FstScopeType::Union => ScopeType::Union,
+        FstScopeType::Class => ScopeType::Class,
+        FstScopeType::Interface => ScopeType::Interface,
+        FstScopeType::Package => ScopeType::Package,
+        FstScopeType::Program => ScopeType::Program,
+        FstScopeType::VhdlArchitecture => ScopeType::VhdlArchitecture,
+        FstScopeType::VhdlProcedure => ScopeType::VhdlProcedure,
+        FstScopeType::VhdlFunction => ScopeType::VhdlFunction,
+        FstScopeType::VhdlRecord => ScopeType::VhdlRecord,
+        FstScopeType::VhdlProcess => ScopeType::VhdlProcess,
+        FstScopeType::VhdlBlock => ScopeType::VhdlBlock,
+        FstScopeType::VhdlForGenerate => ScopeType::VhdlForGenerate,
+        FstScopeType::VhdlIfGenerate => ScopeType::VhdlIfGenerate,
+        FstScopeType::VhdlGenerate => ScopeType::VhdlGenerate,
+        FstScopeType::VhdlPackage => ScopeType::VhdlPackage,
+        FstScopeType::SvArray => ScopeType::SvArray,
+        // these never appear as a `Scope` entry, the attributes have their own entries
+        FstScopeType::AttributeBegin
+        | FstScopeType::AttributeEnd
+        | FstScopeType::VcdScope
+        | FstScopeType::VcdUpScope => ScopeType::Module,
+    }
+}
+
+fn var_type(tpe: FstVarType) -> VarType {
+    match tpe {
+        FstVarType::Event => VarType::Event,
+        FstVarType::Integer => VarType::Integer,
+        FstVarType::Parameter => VarType::Parameter,
+        FstVarType::Real => VarType::Real,
+        FstVarType::RealParameter => VarType::RealParameter,
+        FstVarType::Reg => VarType::Reg,
+        FstVarType::Supply0 => VarType::Supply0,
+        FstVarType::Supply1 => VarType::Supply1,
+        FstVarType::Time => VarType::Time,
+        FstVarType::Tri => VarType::Tri,
+        FstVarType::TriAnd => VarType::TriAnd,
+        FstVarType::TriOr => VarType::TriOr,
+        FstVarType::TriReg => VarType::TriReg,
+        FstVarType::Tri0 => VarType::Tri0,
+        FstVarType::Tri1 => VarType::Tri1,
+        FstVarType::Wand => VarType::WAnd,
+        FstVarType::Wire => VarType::Wire,
+        FstVarType::Wor => VarType::WOr,
+        FstVarType::Port => VarType::Port,
+        FstVarType::SparseArray => VarType::SparseArray,
+        FstVarType::RealTime => VarType::RealTime,
+        FstVarType::GenericString => VarType::String,
+        FstVarType::Bit => VarType::Bit,
+        FstVarType::Logic => VarType::Logic,
+        FstVarType::Int => VarType::Int,
+        FstVarType::ShortInt => VarType::ShortInt,
+        FstVarType::LongInt => VarType::LongInt,
+        FstVarType::Byte => VarType::Byte,
+        FstVarType::Enum => VarType::Enum,
+        FstVarType::ShortReal => VarType::ShortReal,
+    }
+}
+
+fn fst_error(err: fst_reader::ReaderError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+/// The hierarchy of a FST file, made with [`index_fst`]. Signals are read from the file when
+/// they're needed with [`FstIndex::read_signals`].
+#[derive(Debug)]
+pub struct FstIndex {
+    pub vars: Vec<ScopedVar>,
+    pub final_time: u64,
+    pub timescale: Option<Timescale>,
+    /// Problems found in the hierarchy, problems with values are only found reading the signals.
+    pub warnings: Vec<ParseError>,
+    /// The handle index of each var's signal, aliases share their handle with an earlier var like
+    /// a VCD id code used by multiple vars.
+    handles: HashMap<vcd::IdCode, usize>,
+}
+
+/// Read the header and hierarchy of a FST file.
+pub fn index_fst(r: &mut (impl io::BufRead + io::Seek)) -> std::io::Result<FstIndex> {
+    let mut reader = FstReader::open(r).map_err(fst_error)?;
+    let header = reader.get_header();
+
+    let mut vars = vec![];
+    let mut warnings = vec![];
+    let mut handles = HashMap::new();
+    let mut scopes: Vec<(ScopeType, String)> = vec![];
+    reader
+        .read_hierarchy(|entry| match entry {
+            FstHierarchyEntry::Scope { tpe, name, .. } => scopes.push((scope_type(tpe), name)),
+            FstHierarchyEntry::UpScope => {
+                scopes.pop();
+            }
+            FstHierarchyEntry::Var {
+                tpe,
+                name,
+                length,
+                handle,
+                ..
+            } => {
+                // fst keeps the index as part of the name, i.e. `data [7:0]`
+                let (reference, index) = match name.split_once(' ') {
+                    Some((reference, index_str)) => {
+                        let index = index_str.parse().ok();
+                        if index.is_none() {
+                            add_warning(
+                                &mut warnings,
+                                ParseError::binary(format!(
+                                    "ignoring invalid index {index_str:?} of var {reference}"
+                                )),
+                            );
+                        }
+                        (reference.to_owned(), index)
+                    }
+                    None => (name, None),
+                };
+                let code = vcd::IdCode::from(handle.get_index() as u64);
+                handles.insert(code, handle.get_index());
+                vars.push(ScopedVar {
+                    scopes: scopes.clone(),
+                    var: Var {
+                        var_type: var_type(tpe),
+                        size: length,
+                        code,
+                        reference,
+                        index,
+                    },
+                })
+            }
+            _ => (),
+        })
+        .map_err(fst_error)?;
+
+    Ok(FstIndex {
+        vars,
+        final_time: header.end_time,
+        timescale: Some(Timescale {
+            factor: 1,
+            exponent: header.timescale_exponent,
+        }),
+        warnings,
+        handles,
+    })
+}
+
+impl FstIndex {
+    /// Read the signals for `codes` from the file the index was made from. Codes that aren't in
+    /// the hierarchy are left out. Values that don't fit their signal are skipped or truncated
+    /// with a warning added to `warnings`.
+    pub fn read_signals(
+        &self,
+        r: &mut (impl io::BufRead + io::Seek),
+        codes: &[vcd::IdCode],
+        warnings: &mut Vec<ParseError>,
+    ) -> std::io::Result<Vec<(vcd::IdCode, Signal)>> {
+        let codes: HashSet<&vcd::IdCode> = codes.iter().collect();
+        // the first var of a handle decides the type of the signal
+        let mut signals: Vec<(vcd::IdCode, Signal)> = vec![];
+        // the name of that var for warnings
+        let mut names: Vec<&str> = vec![];
+        let mut handle_map: HashMap<usize, usize> = HashMap::new();
+        for item in &self.vars {
+            let code = item.var.code;
+            if !codes.contains(&code) {
+                continue;
+            }
+            handle_map.entry(self.handles[&code]).or_insert_with(|| {
+                signals.push((
+                    code,
+                    if item.var.var_type.is_real() {
+                        Signal::new_real()
+                    } else if item.var.var_type == VarType::String {
+                        Signal::new_string()
+                    } else {
+                        Signal::new(item.var.size as usize)
+                    },
+                ));
+                names.push(&item.var.reference);
+                signals.len() - 1
+            });
+        }
+        if signals.is_empty() {
+            return Ok(signals);
+        }
+
+        let filter = FstFilter::filter_signals(
+            handle_map
+                .keys()
+                .map(|&ix| FstSignalHandle::from_index(ix))
+                .collect(),
+        );
+        r.seek(io::SeekFrom::Start(0))?;
+        let mut reader = FstReader::open(r).map_err(fst_error)?;
+        reader
+            .read_signals(&filter, |time, handle, value| {
+                let Some(&i) = handle_map.get(&handle.get_index()) else {
+                    return;
+                };
+                let signal = &mut signals[i].1;
+                match value {
+                    FstSignalValue::Real(v) if signal.is_real() => signal.insert_real(time, v),
+                    FstSignalValue::String(s) if signal.is_string() => {
+                        signal.insert_string(time, &String::from_utf8_lossy(s))
+                    }
+                    FstSignalValue::String(bits) if !signal.is_real() && !signal.is_string() => {
+                        let mut bits: Vec<Value> =
+                            bits.iter().map(|b| Value::from_ascii(*b)).collect();
+                        if keep_low_bits(&mut bits, signal.width()) {
+                            let msg = format!("value of {} at {time} wider than its var", names[i]);
+                            add_warning(warnings, ParseError::binary(msg));
+                        }
+                        signal.insert(time, bits)
+                    }
+                    _ => {
+                        let msg = format!("value of {} at {time} doesn't match its type", names[i]);
+                        add_warning(warnings, ParseError::binary(msg));
+                    }
+                }
+            })
+            .map_err(fst_error)?;
+
+        for (_, signal) in &mut signals {
+            signal.extend_to(self.final_time);
+        }
+        Ok(signals)
+    }
+}
+
+/// Read a FST file, giving the same result as [`crate::vcd::read_clocked_vcd`].
+pub fn read_fst(r: &mut (impl io::BufRead + io::Seek)) -> std::io::Result<Waveform> {
+    let index = index_fst(r)?;
+    let codes: Vec<vcd::IdCode> = index.vars.iter().map(|v| v.var.code).collect();
+    let mut warnings = index.warnings.clone();
+    let signals: HashMap<vcd::IdCode, Arc<Signal>> = index
+        .read_signals(r, &codes, &mut warnings)?
+        .into_iter()
+        .map(|(code, signal)| (code, Arc::new(signal)))
+        .collect();
+    let vars = index
+        .vars
+        .into_iter()
+        .map(|var| {
+            let signal = Arc::clone(&signals[&var.var.code]);
+            (var, signal)
+        })
+        .collect();
+    Ok(Waveform {
+        vars,
+        final_time: index.final_time,
+        timescale: index.timescale,
+        not_recorded: vec![],
+        warnings,
+    })
+}
@@ -0,0 +1,250 @@
+use crate::vcd::{
+    add_warning, keep_low_bits, ParseError, ScopeType, ScopedVar, Signal, Timescale, Value, Var,
+    VarType, Waveform,
+};
+use std::collections::HashMap;
+use std::io;
+use std::sync::Arc;
+use wellen::{Hierarchy, LoadOptions, SignalValue};
+
+// GHW files are read with wellen, it's the only rust implementation of the format around and the
+// format is undocumented outside of GHDL's source.
+
+fn scope_type(tpe: wellen::ScopeType) -> ScopeType {
+    use wellen::ScopeType as W;
+    match tpe {
+        W::Module => ScopeType::Module,
+        W::Task => ScopeType::Task,
+        W::Function => ScopeType::Function,
+        W::Begin => ScopeType::Begin,
+        W::Fork => ScopeType::Fork,
+        W::Generate => ScopeType::Generate,
+        W::Struct => ScopeType::Struct,
+        W::Union => ScopeType::Union,
+        W::Class => ScopeType::Class,
+        W::Interface => ScopeType::Interface,
+        W::Package => ScopeType::Package,
+        W::Program => ScopeType::Program,
+        W::VhdlArchitecture => ScopeType::VhdlArchitecture,
+        W::VhdlProcedure => ScopeType::VhdlProcedure,
+        W::VhdlFunction => ScopeType::VhdlFunction,
+        W::VhdlRecord => ScopeType::VhdlRecord,
+        W::VhdlProcess => ScopeType::VhdlProcess,
+        W::VhdlBlock => ScopeType::VhdlBlock,
+        W::VhdlForGenerate => ScopeType::VhdlForGenerate,
+        W::VhdlIfGenerate => ScopeType::VhdlIfGenerate,
+        W::VhdlGenerate => ScopeType::VhdlGenerate,
+        W::VhdlPackage => ScopeType::VhdlPackage,
+        W::SvArray => ScopeType::SvArray,
+        W::VhdlArray => ScopeType::VhdlArray,
+        W::GhwGeneric => ScopeType::VhdlGeneric,
+        _ => ScopeType::Module,
+    }
+}
+
+fn var_type(tpe: wellen::VarType) -> VarType {
+    use wellen::VarType as W;
+    match tpe {
+        W::Event => VarType::Event,
+        W::Integer => VarType::Integer,
+        W::Parameter => VarType::Parameter,
+        W::Real => VarType::Real,
+        W::Reg => VarType::Reg,
+        W::Supply0 => VarType::Supply0,
+        W::Supply1 => VarType::Supply1,
+        W::Time => VarType::Time,
+        W::Tri => VarType::Tri,
+        W::TriAnd => VarType::TriAnd,
+        W::TriOr => VarType::TriOr,
+        W::TriReg => VarType::TriReg,
+        W::Tri0 => VarType::Tri0,
+        W::Tri1 => VarType::Tri1,
+        W::WAnd => VarType::WAnd,
+        W::Wire => VarType::Wire,
+        W::WOr => VarType::WOr,
+        W::String => VarType::String,
+        W::Port => VarType::Port,
+        W::SparseArray => VarType::SparseArray,
+        W::RealTime => VarType::RealTime,
+        W::RealParameter => VarType::RealParameter,
+        W::Bit => VarType::Bit,
+        W::Logic => VarType::Logic,
+        W::Int => VarType::Int,
+        W::ShortInt => VarType::ShortInt,
+        W::LongInt => VarType::LongInt,
+        W::Byte => VarType::Byte,
+        W::Enum => VarType::Enum,
+        W::ShortReal => VarType::ShortReal,
+        W::Boolean => VarType::Boolean,
+        W::BitVector => VarType::BitVector,
+        W::StdLogic => VarType::StdLogic,
+        W::StdLogicVector => VarType::StdLogicVector,
+        W::StdULogic => VarType::StdULogic,
+        W::StdULogicVector => VarType::StdULogicVector,
+    }
+}
+
+fn ghw_error(err: wellen::WellenError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+/// Collect the vars of the hierarchy in the same order as they're declared.
+fn hierarchy_vars(h: &Hierarchy) -> Vec<(wellen::VarRef, Vec<(ScopeType, String)>)> {
+    fn add_scope(
+        h: &Hierarchy,
+        vars: &mut Vec<(wellen::VarRef, Vec<(ScopeType, String)>)>,
+        stack: &mut Vec<(ScopeType, String)>,
+        scope: &wellen::Scope,
+    ) {
+        stack.push((scope_type(scope.scope_type()), scope.name(h).to_owned()));
+        vars.extend(scope.vars(h).map(|v| (v, stack.clone())));
+        for child in scope.scopes(h) {
+            add_scope(h, vars, stack, &h[child]);
+        }
+        stack.pop();
+    }
+
+    let mut vars: Vec<_> = h.vars().map(|v| (v, vec![])).collect();
+    for scope in h.scopes() {
+        add_scope(h, &mut vars, &mut vec![], &h[scope]);
+    }
+    vars
+}
+
+/// Read a GHDL GHW file, giving the same result as [`crate::vcd::read_clocked_vcd`].
+///
+/// Vars connected to the same signal share it. VHDL enumerations keep their literal names as labels
+/// on the signal.
+pub fn read_ghw(r: &mut impl io::Read) -> std::io::Result<Waveform> {
+    // wellen wants to own the reader
+    let mut bytes = vec![];
+    r.read_to_end(&mut bytes)?;
+    let options = LoadOptions {
+        multi_thread: false,
+        remove_scopes_with_empty_name: false,
+    };
+    let header =
+        wellen::viewers::read_header(io::Cursor::new(bytes), &options).map_err(ghw_error)?;
+    let h = header.hierarchy;
+    let mut body = wellen::viewers::read_body(header.body, &h, None).map_err(ghw_error)?;
+
+    let mut vars = vec![];
+    let mut signals = vec![];
+    // the name of the var that made each signal for warnings
+    let mut names = vec![];
+    // the index into `signals` for each wellen signal
+    let mut signal_map: HashMap<wellen::SignalRef, usize> = HashMap::new();
+    for (var_ref, scopes) in hierarchy_vars(&h) {
+        let var = &h[var_ref];
+        let index = var.index().map(|i| {
+            if i.msb() == i.lsb() {
+                vcd::ReferenceIndex::BitSelect(i.msb() as i32)
+            } else {
+                vcd::ReferenceIndex::Range(i.msb() as i32, i.lsb() as i32)
+            }
+        });
+        let code = vcd::IdCode::from(var.signal_ref().index() as u64);
+        // reals are declared as 64 bits like VCD does, anything else without a length is one bit
+        let size = var
+            .length()
+            .unwrap_or(if var.is_real() { 64 } else { 1 })
+            .max(1);
+        vars.push((
+            var.signal_ref(),
+            ScopedVar {
+                scopes,
+                var: Var {
+                    var_type: var_type(var.var_type()),
+                    size,
+                    code,
+                    reference: var.name(&h).to_owned(),
+                    index,
+                },
+            },
+        ));
+        // the first var of a signal decides its type
+        if signal_map.contains_key(&var.signal_ref()) {
+            continue;
+        }
+        let mut signal = if var.is_real() {
+            Signal::new_real()
+        } else if var.is_string() {
+            Signal::new_string()
+        } else {
+            Signal::new(size as usize)
+        };
+        if let Some((_, literals)) = var.enum_type(&h) {
+            signal.set_labels(
+                literals
+                    .into_iter()
+                    .map(|(bits, name)| {
+                        let bits = bits.bytes().map(Value::from_ascii).collect();
+                        (bits, name.to_owned())
+                    })
+                    .collect(),
+            );
+        }
+        signal_map.insert(var.signal_ref(), signals.len());
+        signals.push(signal);
+        names.push(var.full_name(&h));
+    }
+
+    let mut warnings = vec![];
+    let ids: Vec<_> = signal_map.keys().copied().collect();
+    for (id, wave) in body.source.load_signals(&ids, &h, false) {
+        let i = signal_map[&id];
+        let signal = &mut signals[i];
+        for (time_ix, value) in wave.iter_changes() {
+            let time = body.time_table[time_ix as usize];
+            let is_bits = !signal.is_real() && !signal.is_string();
+            match value {
+                SignalValue::Real(v) if signal.is_real() => signal.insert_real(time, v),
+                SignalValue::String(s) if signal.is_string() => signal.insert_string(time, s),
+                SignalValue::Event if is_bits => signal.insert(time, vec![Value::V1]),
+                SignalValue::Binary(..)
+                | SignalValue::FourValue(..)
+                | SignalValue::NineValue(..)
+                    if is_bits =>
+                {
+                    let bits = value.to_bit_string().expect("bit values have a bit string");
+                    let mut bits: Vec<Value> = bits.bytes().map(Value::from_ascii).collect();
+                    if keep_low_bits(&mut bits, signal.width()) {
+                        let msg = format!("value of {} at {time} wider than its var", names[i]);
+                        add_warning(&mut warnings, ParseError::binary(msg));
+                    }
+                    signal.insert(time, bits)
+                }
+                _ => {
+                    let msg = format!("value of {} at {time} doesn't match its type", names[i]);
+                    add_warning(&mut warnings, ParseError::binary(msg));
+                }
+            }
+        }
+    }
+
+    let time = body.time_table.last().copied().unwrap_or(0);
+    let signals: Vec<Arc<Signal>> = signals
+        .into_iter()
+        .map(|mut signal| {
+            signal.extend_to(time);
+            Arc::new(signal)
+        })
+        .collect();
+    let vars = vars
+        .into_iter()
+        .map(|(id, var)| (var, Arc::clone(&signals[signal_map[&id]])))
+        .collect();
+    let timescale = h.timescale().and_then(|t| {
+        Some(Timescale {
+            factor: t.factor,
+            exponent: t.unit.to_exponent()?,
+        })
+    });
+    Ok(Waveform {
+        vars,
+        final_time: time,
+        timescale,
+        not_recorded: vec![],
+        warnings,
+    })
+}
@@ -3,8 +3,17 @@
 #![warn(clippy::all, rust_2018_idioms)]
 
 pub mod app;
+pub mod export;
+pub mod expr;
+pub mod fst;
+pub mod ghw;
+pub mod load;
+mod scopes;
+pub mod search;
+pub mod stats;
 pub mod vcd;
 mod wave;
+pub mod wavedrom;
 pub use app::TemplateApp;
 pub mod samples;
 
@@ -0,0 +1,740 @@
+use crate::vcd::{ParseError, ScopedVar, Signal, Timescale, Waveform};
+use crate::{fst, ghw, vcd};
+use ::vcd::IdCode;
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// The waveform file formats that can be read.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Format {
+    Vcd,
+    Fst,
+    Ghw,
+}
+
+impl Format {
+    /// Guess the format from the start of a file.
+    ///
+    /// VCD is text so can never start with a null byte whereas FST always starts with its header
+    /// block (type 0) or, for a gzipped FST, the gzip wrapper block (type 254). GHW files start with
+    /// the magic `GHDLwave\n`.
+    pub fn detect(bytes: &[u8]) -> Format {
+        if bytes.starts_with(b"GHDLwave\n") {
+            return Format::Ghw;
+        }
+        match bytes.first() {
+            Some(0) | Some(254) => Format::Fst,
+            _ => Format::Vcd,
+        }
+    }
+}
+
+/// A compressed wrapper around a waveform file, i.e. a `.vcd.gz`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+    Bzip2,
+}
+
+impl Compression {
+    /// Find the compression from the magic bytes at the start of a file. These are always there so
+    /// the file extension isn't needed.
+    pub fn detect(bytes: &[u8]) -> Option<Compression> {
+        if bytes.starts_with(&[0x1f, 0x8b]) {
+            Some(Compression::Gzip)
+        } else if bytes.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Compression::Zstd)
+        } else if bytes.starts_with(b"BZh") {
+            Some(Compression::Bzip2)
+        } else {
+            None
+        }
+    }
+
+    /// Wrap `r` in a streaming decoder.
+    pub fn decoder<'a>(self, r: impl io::BufRead + 'a) -> io::Result<Box<dyn io::Read + 'a>> {
+        Ok(match self {
+            Compression::Gzip => Box::new(flate2::bufread::MultiGzDecoder::new(r)),
+            Compression::Zstd => Box::new(
+                ruzstd::decoding::StreamingDecoder::new(r)
+                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?,
+            ),
+            Compression::Bzip2 => Box::new(bzip2::bufread::MultiBzDecoder::new(r)),
+        })
+    }
+}
+
+/// Read a waveform file in any of the supported [`Format`]s, optionally compressed with one of the
+/// supported [`Compression`]s.
+pub fn read_waveform(r: &mut (impl io::BufRead + io::Seek)) -> std::io::Result<Waveform> {
+    if let Some(compression) = Compression::detect(r.fill_buf()?) {
+        let mut decoder = io::BufReader::new(compression.decoder(r)?);
+        return read_decompressed(&mut decoder);
+    }
+    match Format::detect(r.fill_buf()?) {
+        Format::Vcd => vcd::read_clocked_vcd(r),
+        Format::Fst => fst::read_fst(r),
+        Format::Ghw => ghw::read_ghw(r),
+    }
+}
+
+fn read_decompressed(r: &mut impl io::BufRead) -> std::io::Result<Waveform> {
+    match Format::detect(r.fill_buf()?) {
+        // VCD is streamed, the others need to seek so are decompressed up front
+        Format::Vcd => vcd::read_clocked_vcd(r),
+        Format::Fst | Format::Ghw => {
+            let mut bytes = vec![];
+            r.read_to_end(&mut bytes)?;
+            read_waveform(&mut io::Cursor::new(bytes))
+        }
+    }
+}
+
+/// A waveform file that's only had its header read, signals are read from the file the first
+/// time they're needed. Only uncompressed VCD and FST files can be read this way.
+#[derive(Debug)]
+pub enum Index {
+    Vcd(std::path::PathBuf, vcd::VcdIndex),
+    Fst(std::path::PathBuf, fst::FstIndex),
+}
+
+impl Index {
+    /// Every declared variable, the signal for each one can be read with its code.
+    pub fn vars(&self) -> &[ScopedVar] {
+        match self {
+            Index::Vcd(_, index) => &index.vars,
+            Index::Fst(_, index) => &index.vars,
+        }
+    }
+
+    pub fn final_time(&self) -> u64 {
+        match self {
+            Index::Vcd(_, index) => index.final_time,
+            Index::Fst(_, index) => index.final_time,
+        }
+    }
+
+    pub fn timescale(&self) -> Option<Timescale> {
+        match self {
+            Index::Vcd(_, index) => index.timescale,
+            Index::Fst(_, index) => index.timescale,
+        }
+    }
+
+    pub fn not_recorded(&self) -> &[std::ops::Range<u64>] {
+        match self {
+            Index::Vcd(_, index) => &index.not_recorded,
+            Index::Fst(..) => &[],
+        }
+    }
+
+    /// Problems found in the file while indexing it.
+    pub fn warnings(&self) -> &[ParseError] {
+        match self {
+            Index::Vcd(_, index) => &index.warnings,
+            Index::Fst(_, index) => &index.warnings,
+        }
+    }
+
+    /// Read the signals with the given codes, reopening the file.
+    pub fn read_signals(
+        &self,
+        codes: &[IdCode],
+        report: impl FnMut(u64) -> io::Result<()>,
+    ) -> io::Result<Signals> {
+        let (Index::Vcd(path, _) | Index::Fst(path, _)) = self;
+        let file = std::fs::File::open(path)?;
+        let mut r = ProgressReader::new(io::BufReader::new(file), report);
+        let mut warnings = vec![];
+        let signals = match self {
+            Index::Vcd(_, index) => index.read_signals(&mut r, codes)?,
+            Index::Fst(_, index) => index.read_signals(&mut r, codes, &mut warnings)?,
+        };
+        Ok(Signals { signals, warnings })
+    }
+}
+
+/// Signals read from an [`Index`] along with their codes.
+#[derive(Debug)]
+pub struct Signals {
+    pub signals: Vec<(IdCode, Signal)>,
+    /// Problems with the values that weren't found making the index. Indexing a VCD file reads
+    /// every value so these are only for FST files.
+    pub warnings: Vec<ParseError>,
+}
+
+/// An opened waveform file, either fully read or just indexed.
+#[derive(Debug)]
+pub enum Opened {
+    Waveform(Waveform),
+    Index(Index),
+}
+
+/// Open a waveform file on disk. If `lazy` is set, uncompressed VCD and FST files are only indexed,
+/// everything else is read in full.
+pub fn open_path(
+    path: &std::path::Path,
+    lazy: bool,
+    report: impl FnMut(u64) -> io::Result<()>,
+) -> io::Result<Opened> {
+    use io::BufRead;
+    let file = std::fs::File::open(path)?;
+    let mut r = ProgressReader::new(io::BufReader::new(file), report);
+    if !lazy || Compression::detect(r.fill_buf()?).is_some() {
+        return read_waveform(&mut r).map(Opened::Waveform);
+    }
+    Ok(match Format::detect(r.fill_buf()?) {
+        Format::Vcd => Opened::Index(Index::Vcd(path.to_owned(), vcd::index_vcd(&mut r)?)),
+        Format::Fst => Opened::Index(Index::Fst(path.to_owned(), fst::index_fst(&mut r)?)),
+        Format::Ghw => Opened::Waveform(ghw::read_ghw(&mut r)?),
+    })
+}
+
+/// How often a [`ProgressReader`] reports, in bytes.
+const REPORT_STEP: u64 = 1 << 20;
+
+/// Wraps a reader to report the number of bytes read so far. The report can return an error to
+/// stop the read, this is how loads get cancelled.
+pub struct ProgressReader<R, F> {
+    inner: R,
+    read: u64,
+    next_report: u64,
+    report: F,
+}
+
+impl<R, F: FnMut(u64) -> io::Result<()>> ProgressReader<R, F> {
+    pub fn new(inner: R, report: F) -> Self {
+        ProgressReader {
+            inner,
+            read: 0,
+            next_report: 0,
+            report,
+        }
+    }
+
+    fn check(&mut self) -> io::Result<()> {
+        if self.read >= self.next_report {
+            self.next_report = self.read + REPORT_STEP;
+            (self.report)(self.read)?;
+        }
+        Ok(())
+    }
+}
+
+impl<R: io::Read, F: FnMut(u64) -> io::Result<()>> io::Read for ProgressReader<R, F> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.check()?;
+        let n = self.inner.read(buf)?;
+        self.read += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: io::BufRead, F: FnMut(u64) -> io::Result<()>> io::BufRead for ProgressReader<R, F> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.check()?;
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.read += amt as u64;
+        self.inner.consume(amt)
+    }
+}
+
+// seeking (only done by FST) doesn't count as progress, it's close enough to assume each byte is
+// read once
+impl<R: io::Seek, F> io::Seek for ProgressReader<R, F> {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+/// The waveform sent back from a web worker. Signals shared between vars are only sent once.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Transfer<S> {
+    vars: Vec<(ScopedVar, usize)>,
+    signals: Vec<S>,
+    final_time: u64,
+    timescale: Option<Timescale>,
+    not_recorded: Vec<std::ops::Range<u64>>,
+    warnings: Vec<ParseError>,
+}
+
+/// Serialize a waveform to send between threads that don't share memory.
+pub fn encode_waveform(waveform: Waveform) -> Vec<u8> {
+    let mut indices = std::collections::HashMap::new();
+    let mut signals: Vec<Arc<Signal>> = vec![];
+    let vars = waveform
+        .vars
+        .into_iter()
+        .map(|(var, signal)| {
+            let ix = *indices.entry(Arc::as_ptr(&signal)).or_insert_with(|| {
+                signals.push(signal);
+                signals.len() - 1
+            });
+            (var, ix)
+        })
+        .collect();
+    let transfer = Transfer {
+        vars,
+        signals: signals.iter().map(|s| &**s).collect::<Vec<&Signal>>(),
+        final_time: waveform.final_time,
+        timescale: waveform.timescale,
+        not_recorded: waveform.not_recorded,
+        warnings: waveform.warnings,
+    };
+    bincode::serialize(&transfer).expect("waveforms are always serializable")
+}
+
+/// The inverse of [`encode_waveform`].
+pub fn decode_waveform(bytes: &[u8]) -> io::Result<Waveform> {
+    let transfer: Transfer<Signal> = bincode::deserialize(bytes)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    let signals: Vec<Arc<Signal>> = transfer.signals.into_iter().map(Arc::new).collect();
+    Ok(Waveform {
+        vars: transfer
+            .vars
+            .into_iter()
+            .map(|(var, ix)| (var, Arc::clone(&signals[ix])))
+            .collect(),
+        final_time: transfer.final_time,
+        timescale: transfer.timescale,
+        not_recorded: transfer.not_recorded,
+        warnings: transfer.warnings,
+    })
+}
+
+/// A web worker along with its message handler, which has to live as long as the worker.
+#[cfg(target_arch = "wasm32")]
+type WorkerHandle = (
+    web_sys::Worker,
+    wasm_bindgen::closure::Closure<dyn FnMut(web_sys::MessageEvent)>,
+);
+
+/// A waveform being loaded in the background, or anything else read from a waveform file such as
+/// the signals of an [`Index`]. Natively this is a thread, on the web it's a web worker.
+pub struct Loading<T = Opened> {
+    /// What's being loaded, i.e. the file name.
+    pub name: String,
+    /// The total number of bytes to read, if it's known.
+    pub total: Option<u64>,
+    read: Arc<AtomicU64>,
+    cancel: Arc<AtomicBool>,
+    result: Arc<Mutex<Option<io::Result<T>>>>,
+    #[cfg(target_arch = "wasm32")]
+    worker: Option<WorkerHandle>,
+}
+
+impl<T> Loading<T> {
+    /// The number of bytes read so far.
+    pub fn bytes_read(&self) -> u64 {
+        self.read.load(Ordering::Relaxed)
+    }
+
+    /// Stop loading. The result is never given.
+    pub fn cancel(self) {
+        self.cancel.store(true, Ordering::Relaxed);
+        #[cfg(target_arch = "wasm32")]
+        if let Some((worker, _)) = &self.worker {
+            worker.terminate();
+        }
+    }
+
+    /// The result of the load if it's finished.
+    pub fn try_finish(&mut self) -> Option<io::Result<T>> {
+        self.result.lock().unwrap().take()
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<T: Send + 'static> Loading<T> {
+    fn spawn(
+        name: String,
+        total: Option<u64>,
+        load: impl FnOnce(&mut dyn FnMut(u64) -> io::Result<()>) -> io::Result<T> + Send + 'static,
+    ) -> Loading<T> {
+        let read = Arc::new(AtomicU64::new(0));
+        let cancel = Arc::new(AtomicBool::new(false));
+        let result = Arc::new(Mutex::new(None));
+        let (read2, cancel2, result2) = (read.clone(), cancel.clone(), result.clone());
+        std::thread::spawn(move || {
+            let loaded = load(&mut |n| {
+                read2.store(n, Ordering::Relaxed);
+                if cancel2.load(Ordering::Relaxed) {
+                    return Err(io::Error::other("loading cancelled"));
+                }
+                Ok(())
+            });
+            if !cancel2.load(Ordering::Relaxed) {
+                *result2.lock().unwrap() = Some(loaded);
+            }
+        });
+        Loading {
+            name,
+            total,
+            read,
+            cancel,
+            result,
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Loading<Signals> {
+    /// Read the signals with the given codes from an indexed file.
+    pub fn read_signals(index: Arc<Index>, codes: Vec<IdCode>) -> Self {
+        let (Index::Vcd(path, _) | Index::Fst(path, _)) = &*index;
+        let total = std::fs::metadata(path).ok().map(|m| m.len());
+        let name = format!("{} signals", codes.len());
+        Loading::spawn(name, total, move |report| {
+            index.read_signals(&codes, report)
+        })
+    }
+}
+
+impl Loading {
+    /// Load a waveform file from disk, only indexing it if `lazy` is set (see [`open_path`]).
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_path(path: std::path::PathBuf, lazy: bool) -> Loading {
+        let total = std::fs::metadata(&path).ok().map(|m| m.len());
+        Loading::spawn(path.display().to_string(), total, move |report| {
+            open_path(&path, lazy, report)
+        })
+    }
+
+    /// Load a waveform file that's already in memory.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_bytes(name: String, bytes: Vec<u8>) -> Loading {
+        let total = Some(bytes.len() as u64);
+        Loading::spawn(name, total, move |report| {
+            read_waveform(&mut ProgressReader::new(io::Cursor::new(bytes), report))
+                .map(Opened::Waveform)
+        })
+    }
+
+    /// Load a waveform file that's already in memory.
+    ///
+    /// The web worker is the `worker` binary which trunk builds next to the app (see
+    /// `index.html`). It says when it's ready, then gets the bytes and sends back progress (as
+    /// numbers) and either the encoded waveform or an error message.
+    #[cfg(target_arch = "wasm32")]
+    pub fn from_bytes(name: String, bytes: Vec<u8>) -> Loading {
+        use wasm_bindgen::JsCast;
+
+        let total = Some(bytes.len() as u64);
+        let read = Arc::new(AtomicU64::new(0));
+        let cancel = Arc::new(AtomicBool::new(false));
+        let result = Arc::new(Mutex::new(None));
+        let worker = match web_sys::Worker::new("./worker_loader.js") {
+            Ok(worker) => worker,
+            Err(err) => {
+                let err = io::Error::other(format!("failed to start web worker: {err:?}"));
+                *result.lock().unwrap() = Some(Err(err));
+                return Loading {
+                    name,
+                    total,
+                    read,
+                    cancel,
+                    result,
+                    worker: None,
+                };
+            }
+        };
+
+        let (read2, result2, worker2) = (read.clone(), result.clone(), worker.clone());
+        let mut bytes = Some(bytes);
+        let on_message = wasm_bindgen::closure::Closure::new(move |msg: web_sys::MessageEvent| {
+            let data = msg.data();
+            if let Some(bytes) = bytes.take() {
+                // the first message says the worker is ready
+                let array = js_sys::Uint8Array::from(&bytes[..]);
+                let transfer = js_sys::Array::of1(&array.buffer());
+                if let Err(err) = worker2.post_message_with_transfer(&array, &transfer) {
+                    let err = io::Error::other(format!("failed to post to web worker: {err:?}"));
+                    *result2.lock().unwrap() = Some(Err(err));
+                }
+            } else if let Some(n) = data.as_f64() {
+                read2.store(n as u64, Ordering::Relaxed);
+            } else if let Some(err) = data.as_string() {
+                *result2.lock().unwrap() = Some(Err(io::Error::other(err)));
+            } else {
+                let encoded = js_sys::Uint8Array::new(&data).to_vec();
+                *result2.lock().unwrap() = Some(decode_waveform(&encoded).map(Opened::Waveform));
+            }
+        });
+        worker.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        Loading {
+            name,
+            total,
+            read,
+            cancel,
+            result,
+            worker: Some((worker, on_message)),
+        }
+    }
+}
+
+/// How long to wait before looking for more of a followed file once everything has been read, and
+/// the shortest time between giving the app its waveform.
+#[cfg(not(target_arch = "wasm32"))]
+const FOLLOW_POLL: std::time::Duration = std::time::Duration::from_millis(200);
+
+/// A VCD file that's still being written, i.e. by a running simulation. A thread keeps reading
+/// whatever gets appended, which works the same for a plain file and a named pipe, and parses it
+/// with a [`vcd::VcdFollower`] so catching up with a big file never holds up the app. The thread
+/// stops when this is dropped.
+pub struct Following {
+    /// The file being followed.
+    pub name: String,
+    // the newest waveform read by the thread, replacing one the app hasn't taken yet
+    latest: Arc<Mutex<Option<io::Result<Waveform>>>>,
+    // whether the app has taken a waveform
+    started: bool,
+    stop: Arc<AtomicBool>,
+}
+
+impl Following {
+    /// Start following the file at `path`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_path(path: std::path::PathBuf) -> Following {
+        let latest = Arc::new(Mutex::new(None));
+        let latest2 = latest.clone();
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop2 = stop.clone();
+        let name = path.display().to_string();
+        std::thread::spawn(move || {
+            if let Err(err) = follow(&path, &stop2, &latest2) {
+                *latest2.lock().unwrap() = Some(Err(err));
+            }
+        });
+        Following {
+            name,
+            latest,
+            started: false,
+            stop,
+        }
+    }
+
+    /// Take the newest waveform read since the last call, if there's been anything new. The signals
+    /// are shared with the thread so should be let go of when the next one is taken.
+    pub fn receive(&mut self) -> io::Result<Option<Waveform>> {
+        let latest = self.latest.lock().unwrap().take().transpose()?;
+        self.started |= latest.is_some();
+        Ok(latest)
+    }
+
+    /// Whether a waveform has been taken, the first is the header read with whatever signals have
+    /// been read so far and the rest are the same vars with signals that have grown.
+    pub fn started(&self) -> bool {
+        self.started
+    }
+}
+
+/// Read and parse the file at `path` as it grows until `stop` is set, putting the waveform in
+/// `latest` every so often.
+#[cfg(not(target_arch = "wasm32"))]
+fn follow(
+    path: &std::path::Path,
+    stop: &AtomicBool,
+    latest: &Mutex<Option<io::Result<Waveform>>>,
+) -> io::Result<()> {
+    use io::Read;
+    use std::time::Instant;
+    // opening a named pipe blocks until the writer opens it too
+    let mut file = std::fs::File::open(path)?;
+    let mut follower = vcd::VcdFollower::new();
+    let mut buf = vec![0; 1 << 16];
+    // whether there's been anything read since the waveform was last given to the app
+    let mut unsent = false;
+    let mut next_send = Instant::now();
+    // the first feed after giving the app the waveform copies the signals it now shares, so
+    // they're given less often once that takes a while
+    let mut copying = false;
+    while !stop.load(Ordering::Relaxed) {
+        match file.read(&mut buf) {
+            // the end for now, the file may still grow (or the pipe get a new writer)
+            Ok(0) if unsent => {
+                std::thread::sleep(next_send.saturating_duration_since(Instant::now()))
+            }
+            Ok(0) => std::thread::sleep(FOLLOW_POLL),
+            Ok(n) => {
+                let start = Instant::now();
+                follower.feed(&buf[..n])?;
+                if std::mem::take(&mut copying) {
+                    next_send = next_send.max(start + start.elapsed() * 4);
+                }
+                // nothing can be shown until the header's all there
+                unsent = follower.has_header();
+            }
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => (),
+            Err(err) => return Err(err),
+        }
+        if unsent && Instant::now() >= next_send {
+            *latest.lock().unwrap() = follower.waveform().map(Ok);
+            next_send = Instant::now() + FOLLOW_POLL;
+            copying = true;
+            unsent = false;
+        }
+    }
+    Ok(())
+}
+
+impl Drop for Following {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// The entry point of the web worker used by [`Loading`].
+#[cfg(target_arch = "wasm32")]
+pub fn run_worker() {
+    use wasm_bindgen::JsCast;
+
+    let scope: web_sys::DedicatedWorkerGlobalScope = js_sys::global().unchecked_into();
+    let scope2 = scope.clone();
+    let on_message =
+        wasm_bindgen::closure::Closure::<dyn FnMut(_)>::new(move |msg: web_sys::MessageEvent| {
+            let bytes = js_sys::Uint8Array::new(&msg.data()).to_vec();
+            let mut report = |n: u64| {
+                scope2
+                    .post_message(&(n as f64).into())
+                    .map_err(|err| io::Error::other(format!("{err:?}")))
+            };
+            let waveform = read_waveform(&mut ProgressReader::new(
+                io::Cursor::new(bytes),
+                &mut report,
+            ));
+            let posted = match waveform {
+                Ok(waveform) => {
+                    let encoded = js_sys::Uint8Array::from(&encode_waveform(waveform)[..]);
+                    let transfer = js_sys::Array::of1(&encoded.buffer());
+                    scope2.post_message_with_transfer(&encoded, &transfer)
+                }
+                Err(err) => scope2.post_message(&err.to_string().into()),
+            };
+            if let Err(err) = posted {
+                log::error!("web worker failed to post result: {err:?}");
+            }
+        });
+    scope.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+    on_message.forget();
+    // let the main thread know we're ready for the bytes
+    scope.post_message(&wasm_bindgen::JsValue::NULL).ok();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Write;
+
+    const VCD: &str = "$scope module top $end
+$var wire 1 ! clk $end
+$upscope $end
+$enddefinitions $end
+#0
+0!
+#5
+1!
+";
+
+    #[test]
+    fn test_compressed() {
+        let mut gz = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+        gz.write_all(VCD.as_bytes()).unwrap();
+        let mut bz = bzip2::write::BzEncoder::new(vec![], bzip2::Compression::default());
+        bz.write_all(VCD.as_bytes()).unwrap();
+        let zst = ruzstd::encoding::compress_to_vec(
+            VCD.as_bytes(),
+            ruzstd::encoding::CompressionLevel::Fastest,
+        );
+        for (bytes, compression) in [
+            (gz.finish().unwrap(), Compression::Gzip),
+            (bz.finish().unwrap(), Compression::Bzip2),
+            (zst, Compression::Zstd),
+        ] {
+            assert_eq!(Compression::detect(&bytes), Some(compression));
+            let wave = read_waveform(&mut io::Cursor::new(bytes)).unwrap();
+            assert_eq!(wave.final_time, 5);
+            assert_eq!(wave.vars[0].0.var.reference, "clk");
+        }
+    }
+
+    #[test]
+    fn test_encode_waveform() {
+        let vcd = "$scope module top $end
+$var wire 1 ! clk $end
+$var wire 1 ! clk_alias $end
+$var wire 4 \" count [3:0] $end
+$upscope $end
+$enddefinitions $end
+#0
+0!
+b0001 \"
+#5
+1!
+";
+        let wave = read_waveform(&mut io::Cursor::new(vcd)).unwrap();
+        let wave = decode_waveform(&encode_waveform(wave)).unwrap();
+        assert_eq!(wave.final_time, 5);
+        assert_eq!(wave.vars.len(), 3);
+        assert!(Arc::ptr_eq(&wave.vars[0].1, &wave.vars[1].1));
+        assert!(matches!(
+            wave.vars[2].0.var.index,
+            Some(::vcd::ReferenceIndex::Range(3, 0))
+        ));
+    }
+
+    #[test]
+    fn test_open_lazy() {
+        let path = std::env::temp_dir().join("waveview_test_open_lazy.vcd");
+        std::fs::write(&path, VCD).unwrap();
+        let opened = open_path(&path, true, |_| Ok(())).unwrap();
+        let Opened::Index(index) = opened else {
+            panic!("expected an index");
+        };
+        assert_eq!(index.final_time(), 5);
+        let code = index.vars()[0].var.code;
+        let signals = index.read_signals(&[code], |_| Ok(())).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(signals.warnings.is_empty());
+        let clk: Vec<_> = signals.signals[0].1.bit_range(0..5).into_iter().collect();
+        assert_eq!(clk, [(0, vcd::Value::V0), (5, vcd::Value::V1)]);
+    }
+
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn test_following() {
+        let path = std::env::temp_dir().join("waveview_test_following.vcd");
+        let (start, rest) = VCD.split_at(VCD.find("#5").unwrap());
+        std::fs::write(&path, start).unwrap();
+        let mut following = Following::from_path(path.clone());
+        // the thread sends what it's read at most every `FOLLOW_POLL`
+        let mut wait_for = |final_time: u64| {
+            for _ in 0..100 {
+                if let Some(waveform) = following.receive().unwrap() {
+                    if waveform.final_time == final_time {
+                        return waveform;
+                    }
+                }
+                std::thread::sleep(std::time::Duration::from_millis(50));
+            }
+            panic!("gave up waiting for time {final_time}");
+        };
+        let waveform = wait_for(0);
+        assert_eq!(waveform.vars.len(), 1);
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(rest.as_bytes())
+            .unwrap();
+        let waveform = wait_for(5);
+        std::fs::remove_file(&path).unwrap();
+        let clk: Vec<_> = waveform.vars[0].1.bit_range(0..5).into_iter().collect();
+        assert_eq!(clk, [(0, vcd::Value::V0), (5, vcd::Value::V1)]);
+        assert!(following.started());
+    }
+}
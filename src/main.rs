@@ -8,6 +8,9 @@ use clap::Parser;
 #[derive(Parser)]
 struct Opt {
     starting_file: Option<std::path::PathBuf>,
+    /// Keep reading the starting VCD file as it's written, i.e. by a running simulation
+    #[arg(long)]
+    follow: bool,
 }
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -30,14 +33,13 @@ fn main() {
         "waveview",
         native_options,
         Box::new(move |cc| {
-            let (signals, time) = if let Some(path) = &opt.starting_file {
-                let file = std::fs::File::open(path).unwrap();
-                let mut buf_file = std::io::BufReader::new(file);
-                waveview::vcd::read_clocked_vcd(&mut buf_file).unwrap()
-            } else {
-                (vec![], 1)
-            };
-            Ok(Box::new(waveview::TemplateApp::new(cc, signals, time)))
+            let mut app = waveview::TemplateApp::new(cc);
+            match opt.starting_file {
+                Some(path) if opt.follow => app.follow_path(path),
+                Some(path) => app.load_path(path),
+                None => (),
+            }
+            Ok(Box::new(app))
         }),
     )
     .unwrap();
@@ -50,8 +52,6 @@ fn main() {
 
     eframe::WebLogger::init(log::LevelFilter::Debug).ok();
 
-    let signals = vec![];
-
     let web_options = eframe::WebOptions::default();
     wasm_bindgen_futures::spawn_local(async {
         eframe::WebRunner::new()
@@ -59,7 +59,7 @@ fn main() {
                 "the_canvas_id", // hardcode it
                 web_options,
                 Box::new(|cc| {
-                    let app = waveview::TemplateApp::new(cc, signals, 1);
+                    let app = waveview::TemplateApp::new(cc);
                     if let Some(Ok(s)) = web_sys::window().map(|w| w.location().search()) {
                         if let Some(url) = s.strip_prefix('?') {
                             let request = ehttp::Request::get(url);
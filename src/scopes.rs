@@ -0,0 +1,163 @@
+use crate::vcd::{ScopeType, ScopedVar};
+use eframe::egui::{self, RichText, Sense, Ui};
+
+/// The scopes of a waveform file as a tree, for picking which of its vars are shown.
+#[derive(Default)]
+pub struct ScopeTree {
+    root: Scope,
+}
+
+#[derive(Default)]
+struct Scope {
+    // `None` for the root
+    scope_type: Option<ScopeType>,
+    name: String,
+    children: Vec<Scope>,
+    // indices of the vars declared directly in the scope
+    vars: Vec<usize>,
+}
+
+impl Scope {
+    /// The vars of this scope and every scope under it, in the order they're shown.
+    fn all_vars(&self, vars: &mut Vec<usize>) {
+        vars.extend(&self.vars);
+        for child in &self.children {
+            child.all_vars(vars);
+        }
+    }
+
+    fn ui(&self, ui: &mut Ui, path: egui::Id, vars: &[ScopedVar], add: &mut Vec<usize>) {
+        for child in &self.children {
+            let id = path.with(&child.name);
+            let scope_type = child.scope_type.unwrap_or(ScopeType::Module);
+            egui::collapsing_header::CollapsingState::load_with_default_open(ui.ctx(), id, false)
+                .show_header(ui, |ui| {
+                    ui.label(icon(scope_type))
+                        .on_hover_text(scope_type.to_string());
+                    ui.label(&child.name);
+                    if ui
+                        .small_button("+")
+                        .on_hover_text("Add every signal in this scope")
+                        .clicked()
+                    {
+                        child.all_vars(add);
+                    }
+                })
+                .body(|ui| child.ui(ui, id, vars, add));
+        }
+        for &i in &self.vars {
+            let var = &vars[i].var;
+            ui.horizontal(|ui| {
+                ui.label(RichText::new(var.var_type.to_string()).small().weak());
+                let mut name = var.reference.clone();
+                if let Some(index) = &var.index {
+                    name.push_str(&index.to_string());
+                }
+                if ui
+                    .add(egui::Label::new(name).sense(Sense::click()))
+                    .on_hover_text("Click to add")
+                    .clicked()
+                {
+                    add.push(i);
+                }
+            });
+        }
+    }
+}
+
+/// An icon for each kind of scope.
+fn icon(scope_type: ScopeType) -> &'static str {
+    use ScopeType::*;
+    match scope_type {
+        Module | Program | VhdlArchitecture => "🔲",
+        Interface => "🔌",
+        Package | VhdlPackage => "📦",
+        Class => "🏛",
+        Task | VhdlProcedure => "⚙",
+        Function | VhdlFunction => "ƒ",
+        Begin | Fork | VhdlBlock | VhdlProcess => "▶",
+        Generate | VhdlForGenerate | VhdlIfGenerate | VhdlGenerate => "🔁",
+        Struct | Union | VhdlRecord => "🗄",
+        SvArray | VhdlArray => "☰",
+        VhdlGeneric => "🔧",
+    }
+}
+
+impl ScopeTree {
+    pub fn new<'a>(vars: impl IntoIterator<Item = &'a ScopedVar>) -> ScopeTree {
+        let mut root = Scope::default();
+        for (i, var) in vars.into_iter().enumerate() {
+            let mut scope = &mut root;
+            for (scope_type, name) in &var.scopes {
+                let ix = match scope
+                    .children
+                    .iter()
+                    .position(|c| c.scope_type == Some(*scope_type) && c.name == *name)
+                {
+                    Some(ix) => ix,
+                    None => {
+                        scope.children.push(Scope {
+                            scope_type: Some(*scope_type),
+                            name: name.clone(),
+                            ..Scope::default()
+                        });
+                        scope.children.len() - 1
+                    }
+                };
+                scope = &mut scope.children[ix];
+            }
+            scope.vars.push(i);
+        }
+        ScopeTree { root }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.children.is_empty() && self.root.vars.is_empty()
+    }
+
+    /// Show the tree, giving the indices of the vars it was built from to add to the view.
+    pub fn ui(&self, ui: &mut Ui, vars: &[ScopedVar]) -> Vec<usize> {
+        let mut add = vec![];
+        self.root
+            .ui(ui, egui::Id::new("scope_tree"), vars, &mut add);
+        add
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vcd::{Var, VarType};
+
+    #[test]
+    fn test_scope_tree() {
+        let var = |scopes: &[&str], name: &str| ScopedVar {
+            scopes: scopes
+                .iter()
+                .map(|s| (ScopeType::Module, s.to_string()))
+                .collect(),
+            var: Var {
+                var_type: VarType::Wire,
+                size: 1,
+                code: ::vcd::IdCode::from(0u32),
+                reference: name.to_owned(),
+                index: None,
+            },
+        };
+        let vars = [
+            var(&["top"], "clk"),
+            var(&["top", "a"], "x"),
+            var(&["top", "b"], "y"),
+            var(&["top", "a"], "z"),
+            var(&[], "global"),
+        ];
+        let tree = ScopeTree::new(&vars);
+        assert_eq!(tree.root.vars, [4]);
+        let top = &tree.root.children[0];
+        let names: Vec<_> = top.children.iter().map(|c| &*c.name).collect();
+        assert_eq!(names, ["a", "b"]);
+        let mut all = vec![];
+        top.all_vars(&mut all);
+        assert_eq!(all, [0, 1, 3, 2]);
+    }
+}
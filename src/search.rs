@@ -0,0 +1,104 @@
+use crate::expr;
+use crate::vcd::{Signal, Value};
+use std::io;
+use std::ops::RangeInclusive;
+
+// Finding the times a bus has a value, i.e. a particular address going past on a bus interface.
+
+/// A value to look for on a bit vector signal. x bits match anything, the others have to be the
+/// same, so a z only matches a z.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Pattern {
+    /// Most significant first, as wide as the signal being searched.
+    bits: Vec<Value>,
+}
+
+impl Pattern {
+    /// Parse a pattern for a `width` bit signal from a verilog number like `8'b1x0x_xxxx`, `'h3f`
+    /// or `12`, or a `0x3f` or `0b101`. Short numbers are extended the way verilog does, with zeros
+    /// or with the top bit if it's x.
+    pub fn parse(text: &str, width: usize) -> io::Result<Pattern> {
+        let text = text.trim();
+        let prefixed = [("0x", "'h"), ("0X", "'h"), ("0b", "'b"), ("0B", "'b")]
+            .into_iter()
+            .find_map(|(prefix, base)| Some(format!("{base}{}", text.strip_prefix(prefix)?)));
+        let bits = expr::parse_number(prefixed.as_deref().unwrap_or(text))?;
+        let extra = bits.len().saturating_sub(width);
+        if bits[..extra]
+            .iter()
+            .any(|&v| !matches!(v, Value::V0 | Value::X))
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("`{text}` doesn't fit in {width} bits"),
+            ));
+        }
+        Ok(Pattern {
+            bits: expr::resize(bits, width),
+        })
+    }
+
+    pub fn matches(&self, value: &[Value]) -> bool {
+        self.bits.len() == value.len()
+            && self
+                .bits
+                .iter()
+                .zip(value)
+                .all(|(&p, &v)| p == Value::X || p == v)
+    }
+
+    /// The first change of `signal` to a matching value strictly after `time`.
+    pub fn next(&self, signal: &Signal, time: u64) -> Option<u64> {
+        signal
+            .changes_where(time.saturating_add(1)..u64::MAX, |v| self.matches(v))
+            .next()
+    }
+
+    /// The last change of `signal` to a matching value strictly before `time`.
+    pub fn prev(&self, signal: &Signal, time: u64) -> Option<u64> {
+        signal
+            .changes_where(0..time, |v| self.matches(v))
+            .next_back()
+    }
+
+    /// Every change of `signal` to a matching value in `range`.
+    pub fn find_all(&self, signal: &Signal, range: RangeInclusive<u64>) -> Vec<u64> {
+        let range = *range.start()..range.end().saturating_add(1);
+        signal.changes_where(range, |v| self.matches(v)).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vcd::bits;
+
+    #[test]
+    fn test_pattern() {
+        let parse = |text: &str| Pattern::parse(text, 8).unwrap();
+        assert!(parse("8'b1x0x_xxxx").matches(&bits("10001111")));
+        assert!(parse("8'b1x0x_xxxx").matches(&bits("1101xxxx")));
+        assert!(!parse("8'b1x0x_xxxx").matches(&bits("10101111")));
+        assert!(parse("'hx3").matches(&bits("11110011")));
+        assert_eq!(parse("12"), parse("0x0c"));
+        assert_eq!(parse("0b1100"), parse("8'd12"));
+        assert!(!parse("'h0z").matches(&bits("00000000")));
+        assert!(Pattern::parse("'h100", 8).is_err());
+        assert!(Pattern::parse("12 cats", 8).is_err());
+
+        let mut addr = Signal::new(8);
+        addr.insert(0, bits("00000000"));
+        addr.insert(5, bits("00010000"));
+        addr.insert(10, bits("00100000"));
+        addr.insert(15, bits("00010001"));
+        addr.extend_to(30);
+        let pattern = parse("'b0001_xxxx");
+        assert_eq!(pattern.next(&addr, 0), Some(5));
+        assert_eq!(pattern.next(&addr, 5), Some(15));
+        assert_eq!(pattern.next(&addr, 15), None);
+        assert_eq!(pattern.prev(&addr, 15), Some(5));
+        assert_eq!(pattern.prev(&addr, 5), None);
+        assert_eq!(pattern.find_all(&addr, 0..=30), [5, 15]);
+        assert_eq!(pattern.find_all(&addr, 6..=15), [15]);
+    }
+}
@@ -0,0 +1,264 @@
+use crate::vcd::{Signal, Value};
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+// Numbers summing up a signal over a range of time, i.e. the duty cycle of an enable or how often
+// each state of a state machine comes up.
+
+/// A value and the time it was held for, clipped to the range being looked at.
+struct Level<T> {
+    value: T,
+    start: u64,
+    end: u64,
+    // whether it started with a change inside the range rather than being the value going in
+    from_change: bool,
+    // whether it ended with a change inside the range rather than at the end of the range
+    to_change: bool,
+}
+
+/// Call `f` with each value held in `range`, from the changes covering it as given by
+/// [`Signal::range`] or [`Signal::bit_range`]. Repeats of the same value are joined up and the time
+/// before the first change isn't at any value.
+fn levels<T: PartialEq>(
+    changes: impl IntoIterator<Item = (u64, T)>,
+    range: &Range<u64>,
+    mut f: impl FnMut(Level<T>),
+) {
+    let mut current: Option<(T, u64, bool)> = None;
+    for (t, value) in changes {
+        if t >= range.end {
+            break;
+        }
+        if current.as_ref().is_some_and(|(v, _, _)| *v == value) {
+            continue;
+        }
+        let start = t.max(range.start);
+        let from_change = t >= range.start && current.is_some();
+        if let Some((value, level_start, level_from_change)) = current.take() {
+            // the value before a change right at the start of the range isn't in it
+            if start > level_start {
+                f(Level {
+                    value,
+                    start: level_start,
+                    end: start,
+                    from_change: level_from_change,
+                    to_change: from_change,
+                });
+            }
+        }
+        current = Some((value, start, from_change));
+    }
+    if let Some((value, start, from_change)) = current {
+        f(Level {
+            value,
+            start,
+            end: range.end,
+            from_change,
+            to_change: false,
+        });
+    }
+}
+
+/// The shortest, longest and total of some lengths of time.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Widths {
+    pub count: usize,
+    pub min: u64,
+    pub max: u64,
+    pub total: u64,
+}
+
+impl Widths {
+    fn add(widths: &mut Option<Widths>, width: u64) {
+        let w = widths.get_or_insert(Widths {
+            count: 0,
+            min: width,
+            max: width,
+            total: 0,
+        });
+        w.count += 1;
+        w.min = w.min.min(width);
+        w.max = w.max.max(width);
+        w.total += width;
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.total as f64 / self.count as f64
+    }
+}
+
+/// The period of a signal that looks like a clock, from the times between its rising edges.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Clock {
+    pub periods: Widths,
+    /// The standard deviation of the periods.
+    pub jitter: f64,
+}
+
+/// How a one bit signal behaves over a range of time.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct BitStats {
+    /// The length of the range.
+    pub duration: u64,
+    pub transitions: usize,
+    /// The time spent at 0, 1, x and z, in that order.
+    pub time_at: [u64; 4],
+    /// The lengths of the low and high pulses that start and end inside the range.
+    pub low: Option<Widths>,
+    pub high: Option<Widths>,
+    /// Set when it has a few rising edges that are all roughly the same distance apart.
+    pub clock: Option<Clock>,
+}
+
+impl BitStats {
+    pub fn new(signal: &Signal, range: Range<u64>) -> BitStats {
+        let mut stats = BitStats {
+            duration: range.end.saturating_sub(range.start),
+            ..BitStats::default()
+        };
+        let mut rising = vec![];
+        levels(signal.bit_range(range.clone()), &range, |level| {
+            let ix = match level.value {
+                Value::V0 => 0,
+                Value::V1 => 1,
+                Value::X => 2,
+                Value::Z => 3,
+            };
+            stats.time_at[ix] += level.end - level.start;
+            if level.from_change {
+                stats.transitions += 1;
+                if level.value == Value::V1 {
+                    rising.push(level.start);
+                }
+            }
+            if level.from_change && level.to_change {
+                match level.value {
+                    Value::V0 => Widths::add(&mut stats.low, level.end - level.start),
+                    Value::V1 => Widths::add(&mut stats.high, level.end - level.start),
+                    _ => (),
+                }
+            }
+        });
+
+        let mut periods = None;
+        for edges in rising.windows(2) {
+            Widths::add(&mut periods, edges[1] - edges[0]);
+        }
+        // a clock's periods are all within a quarter of each other
+        stats.clock = periods
+            .filter(|p| p.count >= 2 && (p.max - p.min) * 4 <= p.min)
+            .map(|periods| {
+                let mean = periods.mean();
+                let variance = rising
+                    .windows(2)
+                    .map(|edges| (edges[1] - edges[0]) as f64 - mean)
+                    .map(|d| d * d)
+                    .sum::<f64>()
+                    / periods.count as f64;
+                Clock {
+                    periods,
+                    jitter: variance.sqrt(),
+                }
+            });
+        stats
+    }
+}
+
+/// How often a bus has each of its values over a range of time.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ValueCount {
+    pub value: Vec<Value>,
+    /// The number of times it changed to the value, counting the value at the start of the range.
+    pub count: usize,
+    pub time: u64,
+}
+
+/// The values a bus has over a range of time.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct BusStats {
+    pub duration: u64,
+    pub transitions: usize,
+    /// Every value it has, the most common first.
+    pub values: Vec<ValueCount>,
+}
+
+impl BusStats {
+    pub fn new(signal: &Signal, range: Range<u64>) -> BusStats {
+        let mut transitions = 0;
+        let mut values: BTreeMap<Vec<Value>, (usize, u64)> = BTreeMap::new();
+        levels(signal.range(range.clone()), &range, |level| {
+            transitions += level.from_change as usize;
+            let (count, time) = values.entry(level.value.to_vec()).or_default();
+            *count += 1;
+            *time += level.end - level.start;
+        });
+        let mut values: Vec<_> = values
+            .into_iter()
+            .map(|(value, (count, time))| ValueCount { value, count, time })
+            .collect();
+        // a stable sort keeps equally common values in order
+        values.sort_by(|a, b| b.count.cmp(&a.count).then(b.time.cmp(&a.time)));
+        BusStats {
+            duration: range.end.saturating_sub(range.start),
+            transitions,
+            values,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vcd::bits;
+
+    #[test]
+    fn test_stats() {
+        let mut clk = Signal::new(1);
+        clk.insert_bit(0, Value::X);
+        for i in 0..5 {
+            clk.insert_bit(10 + i * 10, Value::V1);
+            clk.insert_bit(14 + i * 10, Value::V0);
+        }
+        clk.extend_to(100);
+        let stats = BitStats::new(&clk, 0..100);
+        assert_eq!(stats.transitions, 10);
+        assert_eq!(stats.time_at, [70, 20, 10, 0]);
+        let high = stats.high.unwrap();
+        assert_eq!((high.count, high.min, high.max), (5, 4, 4));
+        // the last low lasts until the end so isn't a whole pulse
+        assert_eq!(stats.low.unwrap().count, 4);
+        let clock = stats.clock.unwrap();
+        assert_eq!(clock.periods.mean(), 10.0);
+        assert_eq!(clock.jitter, 0.0);
+
+        // cutting a pulse in half leaves it out
+        let stats = BitStats::new(&clk, 12..32);
+        assert_eq!(stats.transitions, 4);
+        assert_eq!(stats.time_at, [12, 8, 0, 0]);
+        assert_eq!(stats.high.unwrap().count, 1);
+        assert_eq!(stats.clock, None);
+
+        let mut state = Signal::new(2);
+        state.insert(0, bits("00"));
+        state.insert(10, bits("01"));
+        state.insert(15, bits("10"));
+        state.insert(20, bits("00"));
+        state.insert(30, bits("01"));
+        state.extend_to(40);
+        let stats = BusStats::new(&state, 0..40);
+        assert_eq!(stats.transitions, 4);
+        let counts: Vec<_> = stats
+            .values
+            .iter()
+            .map(|v| (&*v.value, v.count, v.time))
+            .collect();
+        assert_eq!(
+            counts,
+            [
+                (&*bits("00"), 2, 20),
+                (&*bits("01"), 2, 15),
+                (&*bits("10"), 1, 5)
+            ]
+        );
+    }
+}
@@ -1,28 +1,29 @@
 use indexmap::map::IndexMap;
-use std::io;
+use std::io::{self, BufRead};
 // use std::slice::Chunks;
 
 use std::hash::{Hash, Hasher};
+use std::sync::Arc;
 
-use std::collections::{btree_map, BTreeMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
 // pub use vcd::Value;
 
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct Signal {
-    // index into the values
-    ix: BTreeMap<u64, usize>,
+    // the time of each change, in order
+    times: Vec<u64>,
+    // where the value of each change starts in the values
+    offsets: Vec<usize>,
     values: SignalValues,
     width: usize,
+    // names for particular values, i.e. the literals of a VHDL enumeration
+    #[serde(default)]
+    labels: BTreeMap<Vec<Value>, String>,
 }
 
 impl Hash for Signal {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        self.ix.hash(state);
-        // vcd::Value is not hashable, so we have to wrap it
-        // let values: Vec<V> = match &self.values {
-        //     SignalValues::Values(vs) => vs.iter().map(|v| V(*v)).collect(),
-        // };
-        // values.hash(state);
+        self.times.hash(state);
         self.width.hash(state);
     }
 }
@@ -38,6 +39,35 @@ impl From<vcd::Value> for Value {
     }
 }
 
+impl Value {
+    /// The character VCD uses for the value.
+    pub fn to_char(self) -> char {
+        match self {
+            Value::V0 => '0',
+            Value::V1 => '1',
+            Value::X => 'x',
+            Value::Z => 'z',
+        }
+    }
+
+    /// The value for an ascii bit as written by VCD, FST and GHW. This includes the VHDL
+    /// std_logic values which get mapped to the closest 4-state value.
+    pub fn from_ascii(b: u8) -> Value {
+        match b {
+            b'0' | b'l' | b'L' => Value::V0,
+            b'1' | b'h' | b'H' => Value::V1,
+            b'z' | b'Z' => Value::Z,
+            _ => Value::X,
+        }
+    }
+}
+
+/// The values of a string of ascii bits, i.e. `bits("10xz")`, for tests.
+#[cfg(test)]
+pub(crate) fn bits(s: &str) -> Vec<Value> {
+    s.bytes().map(Value::from_ascii).collect()
+}
+
 #[derive(
     Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, serde::Serialize, serde::Deserialize,
 )]
@@ -59,70 +89,441 @@ pub enum Value {
     Z,
 }
 
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
+/// How bit vector values are written as text.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub enum Radix {
+    Binary,
+    #[default]
+    Hex,
+    /// Unsigned decimal.
+    Unsigned,
+    /// Two's complement decimal.
+    Signed,
+}
+
+impl Radix {
+    pub const ALL: [Radix; 4] = [Radix::Binary, Radix::Hex, Radix::Unsigned, Radix::Signed];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Radix::Binary => "Binary",
+            Radix::Hex => "Hex",
+            Radix::Unsigned => "Unsigned",
+            Radix::Signed => "Signed",
+        }
+    }
+
+    /// Write a bit vector, most significant bit first. Hex digits with an x or z in them are
+    /// written as that, decimals are x (or z) if any bit is.
+    pub fn format(self, bits: &[Value]) -> String {
+        let unknown = || {
+            if bits.iter().all(|&v| v == Value::Z) {
+                "z".to_owned()
+            } else {
+                "x".to_owned()
+            }
+        };
+        match self {
+            Radix::Binary => bits.iter().map(|v| v.to_char()).collect(),
+            Radix::Hex => {
+                let mut digits: Vec<char> = bits
+                    .rchunks(4)
+                    .map(|chunk| {
+                        if chunk.iter().all(|&v| v == Value::Z) {
+                            'z'
+                        } else if chunk.iter().any(|&v| matches!(v, Value::X | Value::Z)) {
+                            'x'
+                        } else {
+                            let n = chunk
+                                .iter()
+                                .fold(0, |n, &v| n * 2 + (v == Value::V1) as u32);
+                            char::from_digit(n, 16).unwrap()
+                        }
+                    })
+                    .collect();
+                while digits.len() > 1 && digits.last() == Some(&'0') {
+                    digits.pop();
+                }
+                digits.into_iter().rev().collect()
+            }
+            _ if bits.iter().any(|&v| matches!(v, Value::X | Value::Z)) => unknown(),
+            Radix::Unsigned => decimal(bits.iter().map(|&v| v == Value::V1)),
+            Radix::Signed if bits.first() == Some(&Value::V1) => {
+                // negate by inverting and adding one
+                let mut negated: Vec<bool> = bits.iter().map(|&v| v == Value::V0).collect();
+                for bit in negated.iter_mut().rev() {
+                    *bit = !*bit;
+                    if *bit {
+                        break;
+                    }
+                }
+                format!("-{}", decimal(negated.into_iter()))
+            }
+            Radix::Signed => decimal(bits.iter().map(|&v| v == Value::V1)),
+        }
+    }
+}
+
+/// Bits, most significant first, as a decimal of any size.
+fn decimal(bits: impl Iterator<Item = bool>) -> String {
+    // least significant digit first
+    let mut digits = vec![0u8];
+    for bit in bits {
+        let mut carry = bit as u8;
+        for d in &mut digits {
+            let v = *d * 2 + carry;
+            *d = v % 10;
+            carry = v / 10;
+        }
+        if carry > 0 {
+            digits.push(carry);
+        }
+    }
+    digits.iter().rev().map(|&d| (b'0' + d) as char).collect()
+}
+
+// the (time, offset) of each change
+type Changes<'a> = std::iter::Zip<std::slice::Iter<'a, u64>, std::slice::Iter<'a, usize>>;
+
+/// 4-state values packed into words. Each value takes one bit until an x or z is pushed, after
+/// which they all take two.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+struct PackedValues {
+    words: Vec<u64>,
+    len: usize,
+    four_state: bool,
+}
+
+impl PackedValues {
+    fn bits_per_value(&self) -> usize {
+        if self.four_state {
+            2
+        } else {
+            1
+        }
+    }
+
+    fn push(&mut self, value: Value) {
+        if !self.four_state && matches!(value, Value::X | Value::Z) {
+            self.widen();
+        }
+        let code = match value {
+            Value::V0 => 0,
+            Value::V1 => 1,
+            Value::X => 2,
+            Value::Z => 3,
+        };
+        // values never straddle words because 64 is a multiple of both sizes
+        let bit = self.len * self.bits_per_value();
+        if bit.is_multiple_of(64) {
+            self.words.push(0);
+        }
+        self.words[bit / 64] |= code << (bit % 64);
+        self.len += 1;
+    }
+
+    fn get(&self, ix: usize) -> Value {
+        let bits = self.bits_per_value();
+        let bit = ix * bits;
+        let mask = (1 << bits) - 1;
+        match (self.words[bit / 64] >> (bit % 64)) & mask {
+            0 => Value::V0,
+            1 => Value::V1,
+            2 => Value::X,
+            _ => Value::Z,
+        }
+    }
+
+    /// Switch to two bits per value.
+    fn widen(&mut self) {
+        let old = std::mem::take(self);
+        self.four_state = true;
+        self.words.reserve(old.words.len() * 2);
+        for ix in 0..old.len {
+            self.push(old.get(ix));
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 enum SignalValues {
-    // done in chunks of the signal width
-    Values(Vec<Value>),
-    // Floats(Vec<f64>),
-    // could be single vector of bytes with null terminated strings to reduce allocations
-    // Strings(Vec<String>),
+    // done in chunks of the signal width, offsets count values rather than bits
+    Bits(PackedValues),
+    Floats(Vec<f64>),
+    // a single vector of null terminated strings to reduce allocations, indexed by the start of
+    // each string
+    Strings(Vec<u8>),
 }
 
 impl Signal {
-    pub fn new(width: usize) -> Signal {
+    fn with_values(values: SignalValues, width: usize) -> Signal {
         Signal {
-            ix: BTreeMap::new(),
-            values: SignalValues::Values(vec![]),
+            times: vec![],
+            offsets: vec![],
+            values,
             width,
+            labels: BTreeMap::new(),
         }
     }
 
+    pub fn new(width: usize) -> Signal {
+        Signal::with_values(SignalValues::Bits(PackedValues::default()), width)
+    }
+
+    /// A signal holding `real` values.
+    pub fn new_real() -> Signal {
+        Signal::with_values(SignalValues::Floats(vec![]), 64)
+    }
+
+    /// A signal holding string values.
+    pub fn new_string() -> Signal {
+        Signal::with_values(SignalValues::Strings(vec![]), 0)
+    }
+
     pub fn width(&self) -> usize {
         self.width
     }
 
+    pub fn is_real(&self) -> bool {
+        matches!(self.values, SignalValues::Floats(_))
+    }
+
+    pub fn is_string(&self) -> bool {
+        matches!(self.values, SignalValues::Strings(_))
+    }
+
     pub fn is_empty(&self) -> bool {
-        self.ix.is_empty()
+        self.times.is_empty()
+    }
+
+    /// Set the names given to particular values of the signal.
+    pub fn set_labels(&mut self, labels: BTreeMap<Vec<Value>, String>) {
+        self.labels = labels;
+    }
+
+    /// The name given to a value of the signal, if it has one.
+    pub fn label(&self, value: &[Value]) -> Option<&str> {
+        self.labels.get(value).map(|s| s.as_str())
     }
 
     pub fn final_time(&self) -> u64 {
-        self.ix
-            .iter()
-            .next_back()
-            .as_ref()
-            .map(|x| *x.0)
-            .unwrap_or(0)
+        self.times.last().copied().unwrap_or(0)
+    }
+
+    /// The number of real changes, leaving out the repeat of the last value added by `extend_to`.
+    /// Every real change gets a fresh offset so the repeat is the only one sharing its offset.
+    fn num_changes(&self) -> usize {
+        let n = self.times.len();
+        if n >= 2 && self.offsets[n - 1] == self.offsets[n - 2] {
+            n - 1
+        } else {
+            n
+        }
+    }
+
+    /// The index of the change giving the value at `time`, the last one at or before it.
+    fn change_at(&self, time: u64) -> Option<usize> {
+        self.times.partition_point(|&t| t <= time).checked_sub(1)
+    }
+
+    /// The value of a bit vector signal at `time`, including a change at exactly `time`. `None`
+    /// before the first change or if it isn't a bit vector signal.
+    pub fn value_at(&self, time: u64) -> Option<Vec<Value>> {
+        let values = self.packed()?;
+        let offset = self.offsets[self.change_at(time)?];
+        Some(
+            (offset..offset + self.width)
+                .map(|i| values.get(i))
+                .collect(),
+        )
+    }
+
+    /// The value of a one bit signal at `time`, see [`Signal::value_at`].
+    pub fn bit_at(&self, time: u64) -> Option<Value> {
+        let values = self.packed()?;
+        Some(values.get(self.offsets[self.change_at(time)?]))
+    }
+
+    /// The value of a real signal at `time`, see [`Signal::value_at`].
+    pub fn real_at(&self, time: u64) -> Option<f64> {
+        match &self.values {
+            SignalValues::Floats(vs) => Some(vs[self.offsets[self.change_at(time)?]]),
+            _ => None,
+        }
+    }
+
+    /// The value of a string signal at `time`, see [`Signal::value_at`].
+    pub fn string_at(&self, time: u64) -> Option<&str> {
+        match &self.values {
+            SignalValues::Strings(vs) => Some(string_at(vs, self.offsets[self.change_at(time)?])),
+            _ => None,
+        }
+    }
+
+    /// The time of the last change strictly before `time`.
+    pub fn prev_change(&self, time: u64) -> Option<u64> {
+        let times = &self.times[..self.num_changes()];
+        let ix = times.partition_point(|&t| t < time);
+        ix.checked_sub(1).map(|ix| times[ix])
+    }
+
+    /// The time of the first change strictly after `time`.
+    pub fn next_change(&self, time: u64) -> Option<u64> {
+        let times = &self.times[..self.num_changes()];
+        times.get(times.partition_point(|&t| t <= time)).copied()
+    }
+
+    /// Whether the change at `ix` of a one bit signal goes to `to` from something else. The
+    /// first change counts, like a flop seeing its first edge.
+    fn is_edge(&self, bits: &PackedValues, ix: usize, to: Value) -> bool {
+        bits.get(self.offsets[ix]) == to && (ix == 0 || bits.get(self.offsets[ix - 1]) != to)
+    }
+
+    /// The time of the first change of a one bit signal strictly after `time` going to `to`, i.e.
+    /// the next rising edge for [`Value::V1`]. Steps through the changes from the one found by
+    /// [`Signal::next_change`].
+    pub fn next_edge(&self, time: u64, to: Value) -> Option<u64> {
+        let bits = self.packed()?;
+        let times = &self.times[..self.num_changes()];
+        let start = times.partition_point(|&t| t <= time);
+        (start..times.len())
+            .find(|&ix| self.is_edge(bits, ix, to))
+            .map(|ix| times[ix])
+    }
+
+    /// The time of the last change of a one bit signal strictly before `time` going to `to`, see
+    /// [`Signal::next_edge`].
+    pub fn prev_edge(&self, time: u64, to: Value) -> Option<u64> {
+        let bits = self.packed()?;
+        let times = &self.times[..self.num_changes()];
+        let end = times.partition_point(|&t| t < time);
+        (0..end)
+            .rev()
+            .find(|&ix| self.is_edge(bits, ix, to))
+            .map(|ix| times[ix])
+    }
+
+    /// The times of the changes in `range` of a bit vector signal to a value `f` accepts, in
+    /// order from either end. Only the changes in the range are looked at so searching from a time
+    /// stays quick on long signals. Other kinds of signal have no matching changes.
+    pub fn changes_where<'a>(
+        &'a self,
+        range: std::ops::Range<u64>,
+        mut f: impl FnMut(&[Value]) -> bool + 'a,
+    ) -> impl DoubleEndedIterator<Item = u64> + 'a {
+        let times = &self.times[..self.num_changes()];
+        let start = times.partition_point(|&t| t < range.start);
+        let end = times.partition_point(|&t| t < range.end).max(start);
+        let values = self.packed();
+        let mut value = vec![Value::X; self.width];
+        (start..end)
+            .filter(move |&ix| {
+                let Some(values) = values else {
+                    return false;
+                };
+                let offset = self.offsets[ix];
+                for (i, v) in value.iter_mut().enumerate() {
+                    *v = values.get(offset + i);
+                }
+                f(&value)
+            })
+            .map(move |ix| times[ix])
+    }
+
+    /// The number of changes with a time in `range`.
+    pub fn count_changes(&self, range: std::ops::Range<u64>) -> usize {
+        self.change_times(range).len()
     }
 
-    // pub fn scalars(&self) -> impl Iterator<Item = (u64, Value)> + '_ {
-    //     // assert!(self.width == 1);
-    //     self.values.iter().map(|(&k, ix)| (k, v[0]))
-    // }
+    /// The times of the changes in `range`.
+    pub fn change_times(&self, range: std::ops::Range<u64>) -> &[u64] {
+        let times = &self.times[..self.num_changes()];
+        let start = times.partition_point(|&t| t < range.start);
+        let end = times.partition_point(|&t| t < range.end);
+        &times[start..end.max(start)]
+    }
+
+    /// The indices of the changes covering `range`, including the change before the start (the
+    /// value at the start of the range) and the change after the end.
+    fn covering_range(&self, range: &std::ops::Range<u64>) -> std::ops::Range<usize> {
+        let start = self.times.partition_point(|&t| t < range.start);
+        let end = self.times.partition_point(|&t| t < range.end);
+        start.saturating_sub(1)..(end + 1).min(self.times.len())
+    }
+
+    fn changes(&self, range: &std::ops::Range<u64>) -> Changes<'_> {
+        let ixs = self.covering_range(range);
+        self.times[ixs.clone()].iter().zip(&self.offsets[ixs])
+    }
+
+    fn packed(&self) -> Option<&PackedValues> {
+        match &self.values {
+            SignalValues::Bits(vs) => Some(vs),
+            _ => None,
+        }
+    }
 
-    // pub fn range(&self, range: std::ops::Range<u64>) -> btree_map::Range<'_, u64, Vec<Value>> {
-    //     let lower_bound = *self.values.range(..range.start).next_back().as_ref().unwrap().0;
-    //     self.values.range(lower_bound..range.end)
-    // }
+    fn bits(&self) -> &PackedValues {
+        self.packed().expect("bit access of non-value signal")
+    }
 
     pub fn bit_range(&self, range: std::ops::Range<u64>) -> BitSignalRange<'_> {
+        if !matches!(self.values, SignalValues::Bits(_)) {
+            panic!("bit_range of non-value signal");
+        }
         BitSignalRange {
-            map: &self.ix,
+            signal: self,
             range,
-            values: match &self.values {
-                SignalValues::Values(vs) => vs,
-                // _ => panic!("bit_range"),
-            },
         }
     }
 
     pub fn range(&self, range: std::ops::Range<u64>) -> SignalRange<'_> {
+        if !matches!(self.values, SignalValues::Bits(_)) {
+            panic!("range of non-value signal");
+        }
         SignalRange {
-            map: &self.ix,
+            signal: self,
+            range,
+        }
+    }
+
+    pub fn real_range(&self, range: std::ops::Range<u64>) -> RealSignalRange<'_> {
+        if !self.is_real() {
+            panic!("real_range of non-real signal");
+        }
+        RealSignalRange {
+            signal: self,
+            range,
+        }
+    }
+
+    pub fn string_range(&self, range: std::ops::Range<u64>) -> StringSignalRange<'_> {
+        if !self.is_string() {
+            panic!("string_range of non-string signal");
+        }
+        StringSignalRange {
+            signal: self,
             range,
-            width: self.width,
-            values: match &self.values {
-                SignalValues::Values(vs) => vs,
-            },
+        }
+    }
+
+    /// Record a change at `time` whose value starts at `offset`. Times can only go backwards for a
+    /// signal when reading interleaved files, a change at an existing time replaces it.
+    fn push_change(&mut self, time: u64, offset: usize) {
+        match self.times.last() {
+            Some(&last) if time <= last => {
+                let ix = self.times.partition_point(|&t| t < time);
+                if self.times[ix] == time {
+                    self.offsets[ix] = offset;
+                } else {
+                    self.times.insert(ix, time);
+                    self.offsets.insert(ix, offset);
+                }
+            }
+            _ => {
+                self.times.push(time);
+                self.offsets.push(offset);
+            }
         }
     }
 
@@ -130,129 +531,273 @@ impl Signal {
         if self.width != 1 {
             panic!("insert bit: width {} != 1", self.width);
         }
-        // eprintln!("inserting bit at time {time}");
+        self.insert(time, vec![value]);
+    }
+
+    pub fn insert(&mut self, time: u64, value: Vec<Value>) {
+        assert!(value.len() <= self.width);
+        match &mut self.values {
+            SignalValues::Bits(vs) => {
+                let ix = vs.len;
+                for v in extended(&value, self.width) {
+                    vs.push(v);
+                }
+                self.push_change(time, ix);
+            }
+            _ => panic!("insert into non-value signal"),
+        }
+    }
+
+    pub fn insert_real(&mut self, time: u64, value: f64) {
         match &mut self.values {
-            SignalValues::Values(vs) => {
+            SignalValues::Floats(vs) => {
                 vs.push(value);
                 let ix = vs.len() - 1;
-                self.ix.insert(time, ix);
-            } // _ => panic!("insert_bit into non-value"),
+                self.push_change(time, ix);
+            }
+            _ => panic!("insert_real into non-real signal"),
         }
     }
 
-    pub fn insert(&mut self, time: u64, value: Vec<Value>) {
-        // eprintln!("insert(time = {time}, value = {value:?})");
-        // if self.width != value.len() {
-        //     eprintln!("{} != {}", self.width, value.len());
-        // }
-        assert!(value.len() <= self.width);
+    /// A signal of the bits `bits` of a bit vector signal, counting from the most significant bit.
+    /// It only changes where those bits do. Errors for other kinds of signal or bits it doesn't have.
+    pub fn slice(&self, bits: std::ops::Range<usize>) -> io::Result<Signal> {
+        let Some(values) = self.packed() else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "only bit vector signals can be sliced",
+            ));
+        };
+        if bits.is_empty() || bits.end > self.width {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("no bits {bits:?} in a {} bit signal", self.width),
+            ));
+        }
+        let mut slice = Signal::new(bits.len());
+        let n = self.num_changes();
+        for (&time, &offset) in self.times[..n].iter().zip(&self.offsets) {
+            let value: Vec<Value> = bits.clone().map(|i| values.get(offset + i)).collect();
+            if !slice.ends_with(&value) {
+                slice.insert(time, value);
+            }
+        }
+        slice.extend_to(self.final_time());
+        Ok(slice)
+    }
+
+    /// A bit vector signal of `parts` side by side, the first being the most significant. Parts are
+    /// x before their first change. Errors if there are no parts or any aren't bit vector signals.
+    pub fn concat(parts: &[&Signal]) -> io::Result<Signal> {
+        if parts.is_empty() || parts.iter().any(|p| p.packed().is_none()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "only bit vector signals can be concatenated",
+            ));
+        }
+        let mut signal = Signal::new(parts.iter().map(|p| p.width).sum());
+        let mut times: Vec<u64> = parts
+            .iter()
+            .flat_map(|p| &p.times[..p.num_changes()])
+            .copied()
+            .collect();
+        times.sort_unstable();
+        times.dedup();
+        for time in times {
+            let value: Vec<Value> = parts
+                .iter()
+                .flat_map(|p| p.value_at(time).unwrap_or_else(|| vec![Value::X; p.width]))
+                .collect();
+            if !signal.ends_with(&value) {
+                signal.insert(time, value);
+            }
+        }
+        signal.extend_to(parts.iter().map(|p| p.final_time()).max().unwrap_or(0));
+        Ok(signal)
+    }
+
+    /// Repeat the last value at `time` so the final value is drawn up to the end of the simulation.
+    pub(crate) fn extend_to(&mut self, time: u64) {
+        if let Some(&last_offset) = self.offsets.last() {
+            self.push_change(time, last_offset);
+        }
+    }
+
+    /// Undo `extend_to` so changes after the old final time can be added.
+    fn unextend(&mut self) {
+        let n = self.num_changes();
+        self.times.truncate(n);
+        self.offsets.truncate(n);
+    }
+
+    /// Whether the latest change of a bit vector signal is `value` (extended the same as `insert`).
+    pub(crate) fn ends_with(&self, value: &[Value]) -> bool {
+        match (&self.values, self.offsets.last()) {
+            (SignalValues::Bits(vs), Some(&ix)) => (ix..ix + self.width)
+                .map(|i| vs.get(i))
+                .eq(extended(value, self.width)),
+            _ => false,
+        }
+    }
+
+    /// Whether the latest change of a real signal is `value`.
+    fn ends_with_real(&self, value: f64) -> bool {
+        match (&self.values, self.offsets.last()) {
+            (SignalValues::Floats(vs), Some(&ix)) => vs[ix] == value,
+            _ => false,
+        }
+    }
+
+    /// Whether the latest change of a string signal is `value`.
+    fn ends_with_string(&self, value: &str) -> bool {
+        match (&self.values, self.offsets.last()) {
+            (SignalValues::Strings(vs), Some(&ix)) => {
+                vs[ix..].split(|&b| b == 0).next() == Some(value.as_bytes())
+            }
+            _ => false,
+        }
+    }
+
+    /// Insert a string value. Anything after a null byte in `value` is lost.
+    pub fn insert_string(&mut self, time: u64, value: &str) {
         match &mut self.values {
-            SignalValues::Values(vs) => {
+            SignalValues::Strings(vs) => {
                 let ix = vs.len();
-                vs.extend(value.iter().cloned());
-                for _ in 0..(self.width - value.len()) {
-                    vs.push(Value::V0);
-                }
-                self.ix.insert(time, ix);
+                vs.extend_from_slice(value.as_bytes());
+                vs.push(0);
+                self.push_change(time, ix);
             }
+            _ => panic!("insert_string into non-string signal"),
         }
     }
 }
 
-impl std::ops::Index<u64> for Signal {
-    type Output = [Value];
-    fn index(&self, index: u64) -> &[Value] {
-        self.range(0..index)
-            .into_iter()
-            .next_back()
-            .as_ref()
-            .unwrap()
-            .1
-    }
+/// Drop the bits of a value read from a file above the `width` of its var, giving whether there
+/// were any.
+pub(crate) fn keep_low_bits(value: &mut Vec<Value>, width: usize) -> bool {
+    let extra = value.len().saturating_sub(width);
+    value.drain(..extra);
+    extra > 0
+}
+
+/// `value` extended on the left to `width` bits the way VCD does, with zeros or with the top bit if
+/// it's x or z.
+fn extended(value: &[Value], width: usize) -> impl Iterator<Item = Value> + '_ {
+    let fill = match value.first() {
+        Some(&v @ (Value::X | Value::Z)) => v,
+        _ => Value::V0,
+    };
+    std::iter::repeat_n(fill, width.saturating_sub(value.len())).chain(value.iter().copied())
 }
 
 pub struct SignalRange<'a> {
-    map: &'a BTreeMap<u64, usize>,
+    signal: &'a Signal,
     range: std::ops::Range<u64>,
+}
+
+/// A value of a bit vector signal borrowed from where the signal keeps it, most significant bit
+/// first.
+#[derive(Clone, Copy)]
+pub struct PackedBits<'a> {
+    values: &'a PackedValues,
+    offset: usize,
     width: usize,
-    values: &'a [Value],
+}
+
+impl<'a> PackedBits<'a> {
+    pub fn len(&self) -> usize {
+        self.width
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width == 0
+    }
+
+    pub fn get(&self, i: usize) -> Value {
+        assert!(i < self.width, "bit {i} of {} bit value", self.width);
+        self.values.get(self.offset + i)
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = Value> + ExactSizeIterator + 'a {
+        let values = self.values;
+        (self.offset..self.offset + self.width).map(move |i| values.get(i))
+    }
+
+    pub fn to_vec(&self) -> Vec<Value> {
+        self.iter().collect()
+    }
+}
+
+impl PartialEq for PackedBits<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.width == other.width && self.iter().eq(other.iter())
+    }
+}
+
+impl std::fmt::Debug for PackedBits<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
 }
 
 pub struct SignalRangeIter<'a> {
-    iter: btree_map::Range<'a, u64, usize>,
+    changes: Changes<'a>,
     width: usize,
-    values: &'a [Value],
+    values: &'a PackedValues,
+}
+
+impl<'a> SignalRangeIter<'a> {
+    fn value(&self, offset: usize) -> PackedBits<'a> {
+        PackedBits {
+            values: self.values,
+            offset,
+            width: self.width,
+        }
+    }
 }
 
 impl<'a> Iterator for SignalRangeIter<'a> {
-    type Item = (u64, &'a [Value]);
+    type Item = (u64, PackedBits<'a>);
     fn next(&mut self) -> Option<Self::Item> {
-        let (t, ix) = self.iter.next()?;
-        // eprintln!("let (t = {t}, ix = {ix}) = self.iter.next()?;");
-        // let max_ix = (self.values.len() / self.width) - 1; // XXX THIS IS WRONG, WHAT'S GOING ON HERE?
-        let start = *ix;
-        let end = start + self.width;
-        Some((*t, &self.values[start..end]))
+        let (&t, &offset) = self.changes.next()?;
+        Some((t, self.value(offset)))
     }
 }
 
 impl<'a> DoubleEndedIterator for SignalRangeIter<'a> {
     fn next_back(&mut self) -> Option<Self::Item> {
-        let (t, ix) = self.iter.next_back()?;
-        let start = *ix;
-        let end = start + self.width;
-        Some((*t, &self.values[start..end]))
+        let (&t, &offset) = self.changes.next_back()?;
+        Some((t, self.value(offset)))
     }
 }
 
-// impl<'a> Index<u64> for SignalRange<'a> {
-//     type Output = &'a [Value];
-//     fn index(&self, index: u64) -> &[Value] {
-//         self.map.range(..index).next_back().as_ref().unwrap().1
-//     }
-// }
-
 impl<'a> IntoIterator for SignalRange<'a> {
-    type Item = (u64, &'a [Value]);
+    type Item = (u64, PackedBits<'a>);
     type IntoIter = SignalRangeIter<'a>;
     fn into_iter(self) -> Self::IntoIter {
-        let lower_bound = self
-            .map
-            .range(..self.range.start)
-            .next_back()
-            .as_ref()
-            .map_or(0, |v| *v.0);
-        let upper_bound = self
-            .map
-            .range(self.range.end..)
-            .next()
-            .as_ref()
-            .map_or(u64::MAX - 1, |v| *v.0);
-        let iter = self.map.range(lower_bound..upper_bound + 1);
         SignalRangeIter {
-            iter,
-            width: self.width,
-            values: self.values,
+            changes: self.signal.changes(&self.range),
+            width: self.signal.width,
+            values: self.signal.bits(),
         }
     }
 }
 
 pub struct BitSignalRange<'a> {
-    map: &'a BTreeMap<u64, usize>,
+    signal: &'a Signal,
     range: std::ops::Range<u64>,
-    values: &'a [Value],
 }
 
 pub struct BitSignalRangeIter<'a> {
-    iter: btree_map::Range<'a, u64, usize>,
-    values: &'a [Value],
+    changes: Changes<'a>,
+    values: &'a PackedValues,
 }
 
 impl<'a> Iterator for BitSignalRangeIter<'a> {
     type Item = (u64, Value);
     fn next(&mut self) -> Option<Self::Item> {
-        let (t, ix) = self.iter.next()?;
-        Some((*t, self.values[*ix]))
+        let (&t, &offset) = self.changes.next()?;
+        Some((t, self.values.get(offset)))
     }
 }
 
@@ -260,153 +805,1781 @@ impl<'a> IntoIterator for BitSignalRange<'a> {
     type Item = (u64, Value);
     type IntoIter = BitSignalRangeIter<'a>;
     fn into_iter(self) -> Self::IntoIter {
-        let lower_bound = self
-            .map
-            .range(..self.range.start)
-            .next_back()
-            .as_ref()
-            .map_or(0, |v| *v.0);
-        let upper_bound = self
-            .map
-            .range(self.range.end..)
-            .next()
-            .as_ref()
-            .map_or(u64::MAX - 1, |v| *v.0);
-        let iter = self.map.range(lower_bound..upper_bound + 1);
         BitSignalRangeIter {
-            iter,
-            values: self.values,
+            changes: self.signal.changes(&self.range),
+            values: self.signal.bits(),
         }
     }
 }
 
-#[derive(Debug)]
-pub struct ScopedVar {
-    pub scopes: Vec<(vcd::ScopeType, String)>,
-    pub var: vcd::Var,
+pub struct RealSignalRange<'a> {
+    signal: &'a Signal,
+    range: std::ops::Range<u64>,
 }
 
-fn header_vars(items: &[vcd::ScopeItem]) -> Vec<ScopedVar> {
-    let mut vars = vec![];
+pub struct RealSignalRangeIter<'a> {
+    changes: Changes<'a>,
+    values: &'a [f64],
+}
 
-    fn add_scopes(
-        vars: &mut Vec<ScopedVar>,
-        stack: &[(vcd::ScopeType, String)],
-        scope_item: &vcd::ScopeItem,
-    ) {
-        match scope_item {
-            vcd::ScopeItem::Var(var) => vars.push(ScopedVar {
-                scopes: stack.to_vec(),
-                var: var.clone(),
-            }),
-            vcd::ScopeItem::Scope(scope) => {
-                let mut stack = stack.to_vec();
-                stack.push((scope.scope_type, scope.identifier.clone()));
-                scope
-                    .items
-                    .iter()
-                    .for_each(|item| add_scopes(vars, &stack, item));
-            }
-            vcd::ScopeItem::Comment(_) => {}
-            _ => (),
+impl<'a> Iterator for RealSignalRangeIter<'a> {
+    type Item = (u64, f64);
+    fn next(&mut self) -> Option<Self::Item> {
+        let (&t, &ix) = self.changes.next()?;
+        Some((t, self.values[ix]))
+    }
+}
+
+impl<'a> IntoIterator for RealSignalRange<'a> {
+    type Item = (u64, f64);
+    type IntoIter = RealSignalRangeIter<'a>;
+    fn into_iter(self) -> Self::IntoIter {
+        RealSignalRangeIter {
+            changes: self.signal.changes(&self.range),
+            values: match &self.signal.values {
+                SignalValues::Floats(vs) => vs,
+                _ => &[],
+            },
         }
     }
+}
+
+/// The type of a variable as given by its `$var` declaration.
+///
+/// This covers the types in the VCD spec along with the extensions GTKWave accepts, which is what
+/// Verilator, GHDL and most SystemVerilog simulators write.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, serde::Serialize, serde::Deserialize)]
+pub enum VarType {
+    Event,
+    Integer,
+    Parameter,
+    Real,
+    RealParameter,
+    Reg,
+    Supply0,
+    Supply1,
+    Time,
+    Tri,
+    TriAnd,
+    TriOr,
+    TriReg,
+    Tri0,
+    Tri1,
+    WAnd,
+    Wire,
+    WOr,
+    String,
+    Port,
+    SparseArray,
+    RealTime,
+    // SystemVerilog
+    Bit,
+    Logic,
+    Int,
+    ShortInt,
+    LongInt,
+    Byte,
+    Enum,
+    ShortReal,
+    // VHDL
+    Boolean,
+    BitVector,
+    StdLogic,
+    StdLogicVector,
+    StdULogic,
+    StdULogicVector,
+}
 
-    items
-        .iter()
-        .for_each(|item| add_scopes(&mut vars, &[], item));
+impl VarType {
+    /// Whether values of this type are written with `r` real value changes.
+    pub fn is_real(self) -> bool {
+        matches!(
+            self,
+            VarType::Real | VarType::RealParameter | VarType::RealTime | VarType::ShortReal
+        )
+    }
+}
 
-    // for var in &vars {
-    //     eprintln!("var = {var:?}");
-    // }
+const VAR_TYPE_NAMES: &[(&str, VarType)] = &[
+    ("event", VarType::Event),
+    ("integer", VarType::Integer),
+    ("parameter", VarType::Parameter),
+    ("real", VarType::Real),
+    ("real_parameter", VarType::RealParameter),
+    ("reg", VarType::Reg),
+    ("supply0", VarType::Supply0),
+    ("supply1", VarType::Supply1),
+    ("time", VarType::Time),
+    ("tri", VarType::Tri),
+    ("triand", VarType::TriAnd),
+    ("trior", VarType::TriOr),
+    ("trireg", VarType::TriReg),
+    ("tri0", VarType::Tri0),
+    ("tri1", VarType::Tri1),
+    ("wand", VarType::WAnd),
+    ("wire", VarType::Wire),
+    ("wor", VarType::WOr),
+    ("string", VarType::String),
+    ("port", VarType::Port),
+    ("sparray", VarType::SparseArray),
+    ("realtime", VarType::RealTime),
+    ("bit", VarType::Bit),
+    ("logic", VarType::Logic),
+    ("int", VarType::Int),
+    ("shortint", VarType::ShortInt),
+    ("longint", VarType::LongInt),
+    ("byte", VarType::Byte),
+    ("enum", VarType::Enum),
+    ("shortreal", VarType::ShortReal),
+    ("boolean", VarType::Boolean),
+    ("bit_vector", VarType::BitVector),
+    ("std_logic", VarType::StdLogic),
+    ("std_logic_vector", VarType::StdLogicVector),
+    ("std_ulogic", VarType::StdULogic),
+    ("std_ulogic_vector", VarType::StdULogicVector),
+];
 
-    vars
+impl std::str::FromStr for VarType {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, ()> {
+        VAR_TYPE_NAMES
+            .iter()
+            .find(|(name, _)| *name == s)
+            .map(|(_, t)| *t)
+            .ok_or(())
+    }
 }
 
-pub fn read_clocked_vcd(
-    r: &mut impl io::BufRead,
-) -> std::io::Result<(Vec<(ScopedVar, Signal)>, u64)> {
-    let mut parser = vcd::Parser::new(r);
+impl std::fmt::Display for VarType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (name, _) = VAR_TYPE_NAMES.iter().find(|(_, t)| t == self).unwrap();
+        f.write_str(name)
+    }
+}
 
-    // The VCD spec is weird and confusing. There's a couple of features I'm not bothering to
-    // impliment yet (and probably others I've missed or misunderstood):
-    //
-    //   - the spec allows multiple variables to have the same identifier code which I don't support
-    //     (shouldn't be too difficuilt to add)
-    //   - the reference indexes are ignored, I don't really understand why you'd want it and it's
-    //     annoying the resolve the types (but also shouldn't be that difficult)
-    //
-    // I assume that time isn't allowed to go backwards but I don't think this is explicit in the
-    // spec. This implimentation allows going backwards in time only to change signals whose values
-    // for a later time haven't yet been written. i.e. each individual signal needs monotonous times
-    // but this can be interleaved in the vcd files.
+/// The type of a scope as given by its `$scope` declaration.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, serde::Serialize, serde::Deserialize)]
+pub enum ScopeType {
+    Module,
+    Task,
+    Function,
+    Begin,
+    Fork,
+    Generate,
+    Struct,
+    Union,
+    Class,
+    Interface,
+    Package,
+    Program,
+    VhdlArchitecture,
+    VhdlProcedure,
+    VhdlFunction,
+    VhdlRecord,
+    VhdlProcess,
+    VhdlBlock,
+    VhdlForGenerate,
+    VhdlIfGenerate,
+    VhdlGenerate,
+    VhdlPackage,
+    VhdlArray,
+    VhdlGeneric,
+    SvArray,
+}
 
-    // Parse the header and find the wires
-    let header = match parser.parse_header() {
-        Ok(header) => header,
-        Err(err) => {
-            if let Some(err2) = err.get_ref() {
-                if let Some(err) = err2.downcast_ref::<vcd::ParseError>() {
-                    log::warn!("vcd header parse error: {}", err);
-                }
-                return Err(err);
-            } else {
-                return Err(err);
-            }
-        }
-    };
-    let mut id_map: IndexMap<vcd::IdCode, ScopedVar> = IndexMap::new();
-    let mut signal_map: IndexMap<vcd::IdCode, Signal> = IndexMap::new();
+const SCOPE_TYPE_NAMES: &[(&str, ScopeType)] = &[
+    ("module", ScopeType::Module),
+    ("task", ScopeType::Task),
+    ("function", ScopeType::Function),
+    ("begin", ScopeType::Begin),
+    ("fork", ScopeType::Fork),
+    ("generate", ScopeType::Generate),
+    ("struct", ScopeType::Struct),
+    ("union", ScopeType::Union),
+    ("class", ScopeType::Class),
+    ("interface", ScopeType::Interface),
+    ("package", ScopeType::Package),
+    ("program", ScopeType::Program),
+    ("vhdl_architecture", ScopeType::VhdlArchitecture),
+    ("vhdl_procedure", ScopeType::VhdlProcedure),
+    ("vhdl_function", ScopeType::VhdlFunction),
+    ("vhdl_record", ScopeType::VhdlRecord),
+    ("vhdl_process", ScopeType::VhdlProcess),
+    ("vhdl_block", ScopeType::VhdlBlock),
+    ("vhdl_for_generate", ScopeType::VhdlForGenerate),
+    ("vhdl_if_generate", ScopeType::VhdlIfGenerate),
+    ("vhdl_generate", ScopeType::VhdlGenerate),
+    ("vhdl_package", ScopeType::VhdlPackage),
+    ("vhdl_array", ScopeType::VhdlArray),
+    ("vhdl_generic", ScopeType::VhdlGeneric),
+    ("sv_array", ScopeType::SvArray),
+];
 
-    for item in header_vars(&header.items) {
-        match item.var.var_type {
-            vcd::VarType::Reg | vcd::VarType::Wire => (),
-            _ => continue,
-        }
-        signal_map.insert(item.var.code, Signal::new(item.var.size as usize));
-        id_map.insert(item.var.code, item);
+impl std::str::FromStr for ScopeType {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, ()> {
+        SCOPE_TYPE_NAMES
+            .iter()
+            .find(|(name, _)| *name == s)
+            .map(|(_, t)| *t)
+            .ok_or(())
     }
+}
 
-    let mut time = 0;
+impl std::fmt::Display for ScopeType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (name, _) = SCOPE_TYPE_NAMES.iter().find(|(_, t)| t == self).unwrap();
+        f.write_str(name)
+    }
+}
 
-    while let Some(command) = parser.next() {
-        use vcd::Command::*;
-        match command {
-            Ok(Timestamp(t)) => time = t,
-            Ok(ChangeScalar(i, v)) => match signal_map.get_mut(&i) {
-                Some(signal) => signal.insert(time, vec![v.into()]),
-                None => log::warn!("ChangeScalar id {i:?} not found"),
-            },
-            Ok(ChangeVector(i, v)) => {
-                // panic!("can't change vector yet");
-                if let Some(signal) = signal_map.get_mut(&i) {
-                    signal.insert(time, v.iter().map(|x| x.into()).collect());
-                } else {
-                    log::warn!("id {i:?} not found");
-                }
+/// A variable from a `$var` declaration.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Var {
+    pub var_type: VarType,
+    pub size: u32,
+    #[serde(with = "as_string")]
+    pub code: vcd::IdCode,
+    pub reference: String,
+    #[serde(with = "option_as_string")]
+    pub index: Option<vcd::ReferenceIndex>,
+}
+
+// the vcd crate types don't implement serde but they can be written the same as they are in a VCD
+pub(crate) mod as_string {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+    use std::fmt::Display;
+    use std::str::FromStr;
+
+    pub fn serialize<T: Display, S: Serializer>(value: &T, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_str(value)
+    }
+
+    pub fn deserialize<'de, T, D>(d: D) -> Result<T, D::Error>
+    where
+        T: FromStr,
+        T::Err: Display,
+        D: Deserializer<'de>,
+    {
+        String::deserialize(d)?.parse().map_err(D::Error::custom)
+    }
+}
+
+mod option_as_string {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+    use std::fmt::Display;
+    use std::str::FromStr;
+
+    pub fn serialize<T: Display, S: Serializer>(
+        value: &Option<T>,
+        s: S,
+    ) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(value) => s.serialize_some(&value.to_string()),
+            None => s.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, T, D>(d: D) -> Result<Option<T>, D::Error>
+    where
+        T: FromStr,
+        T::Err: Display,
+        D: Deserializer<'de>,
+    {
+        Option::<String>::deserialize(d)?
+            .map(|s| s.parse().map_err(D::Error::custom))
+            .transpose()
+    }
+}
+
+/// The null terminated string starting at `ix`.
+fn string_at(values: &[u8], ix: usize) -> &str {
+    let bytes = &values[ix..];
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    // only whole strings are inserted and null is a single byte so this is always valid
+    std::str::from_utf8(&bytes[..end]).unwrap_or_default()
+}
+
+pub struct StringSignalRange<'a> {
+    signal: &'a Signal,
+    range: std::ops::Range<u64>,
+}
+
+pub struct StringSignalRangeIter<'a> {
+    changes: Changes<'a>,
+    values: &'a [u8],
+}
+
+impl<'a> Iterator for StringSignalRangeIter<'a> {
+    type Item = (u64, &'a str);
+    fn next(&mut self) -> Option<Self::Item> {
+        let (&t, &ix) = self.changes.next()?;
+        Some((t, string_at(self.values, ix)))
+    }
+}
+
+impl<'a> IntoIterator for StringSignalRange<'a> {
+    type Item = (u64, &'a str);
+    type IntoIter = StringSignalRangeIter<'a>;
+    fn into_iter(self) -> Self::IntoIter {
+        StringSignalRangeIter {
+            changes: self.signal.changes(&self.range),
+            values: match &self.signal.values {
+                SignalValues::Strings(vs) => vs,
+                _ => &[],
+            },
+        }
+    }
+}
+
+/// A loaded waveform file.
+#[derive(Debug, Default)]
+pub struct Waveform {
+    /// Every variable with its signal, variables with the same id code share a signal.
+    pub vars: Vec<(ScopedVar, Arc<Signal>)>,
+    /// The time of the last change.
+    pub final_time: u64,
+    /// The length of a tick, if the file gave one.
+    pub timescale: Option<Timescale>,
+    /// The times where dumping was turned off with `$dumpoff`, the values here aren't known.
+    pub not_recorded: Vec<std::ops::Range<u64>>,
+    /// Problems in the file that were skipped over.
+    pub warnings: Vec<ParseError>,
+}
+
+/// A problem in a waveform file and where it is. Errors that stop a file loading are given as an
+/// [`io::Error`] of kind [`io::ErrorKind::InvalidData`] wrapping one of these.
+#[derive(Clone, PartialEq, Eq, Debug, serde::Serialize, serde::Deserialize)]
+pub struct ParseError {
+    /// The line in the file, starting from 1. Binary formats like FST have no lines so give 0.
+    pub line: u64,
+    /// The byte offset from the start of the file, 0 for binary formats.
+    pub offset: u64,
+    /// The token the problem is with, if it's about a particular one.
+    pub token: Option<String>,
+    pub msg: String,
+}
+
+impl ParseError {
+    /// The parse error behind an [`io::Error`], if there is one.
+    pub fn from_io(err: &io::Error) -> Option<&ParseError> {
+        err.get_ref()?.downcast_ref()
+    }
+
+    /// A problem in a binary file, which doesn't have a line to point at.
+    pub(crate) fn binary(msg: impl Into<String>) -> ParseError {
+        ParseError {
+            line: 0,
+            offset: 0,
+            token: None,
+            msg: msg.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.line != 0 {
+            write!(f, "line {}: ", self.line)?;
+        }
+        write!(f, "{}", self.msg)?;
+        if let Some(token) = &self.token {
+            write!(f, " ({token:?})")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl From<ParseError> for io::Error {
+    fn from(err: ParseError) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, err)
+    }
+}
+
+/// Only this many warnings are kept, a broken file could otherwise give one for every line.
+const MAX_WARNINGS: usize = 1000;
+
+pub(crate) fn add_warning(warnings: &mut Vec<ParseError>, warning: ParseError) {
+    log::warn!("{warning}");
+    if warnings.len() < MAX_WARNINGS {
+        warnings.push(warning);
+    }
+}
+
+/// Counts the bytes consumed from a reader so problems can give their offset.
+struct Counted<R> {
+    inner: R,
+    pos: u64,
+}
+
+impl<R: io::Read> io::Read for Counted<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: io::BufRead> io::BufRead for Counted<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos += amt as u64;
+        self.inner.consume(amt)
+    }
+}
+
+/// The length of one tick, `factor * 10^exponent` seconds.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, serde::Serialize, serde::Deserialize)]
+pub struct Timescale {
+    pub factor: u32,
+    pub exponent: i8,
+}
+
+const TIME_UNITS: &[(i8, &str)] = &[
+    (0, "s"),
+    (-3, "ms"),
+    (-6, "us"),
+    (-9, "ns"),
+    (-12, "ps"),
+    (-15, "fs"),
+    (-18, "as"),
+    (-21, "zs"),
+];
+
+impl Timescale {
+    /// The length of one tick in seconds.
+    pub fn seconds(self) -> f64 {
+        self.factor as f64 * 10f64.powi(self.exponent as i32)
+    }
+
+    /// Format a number of ticks as a time in the largest unit that keeps it at least 1, i.e.
+    /// 1500 ticks of `1ps` is `1.5 ns`. At most 3 decimal places are shown.
+    pub fn format(self, ticks: i64) -> String {
+        // work in zeptoseconds so everything stays an integer
+        let pow = |exp: i8| 10i128.pow((exp as i32 + 21).clamp(0, 38) as u32);
+        let zs = (ticks as i128)
+            .saturating_mul(self.factor as i128)
+            .saturating_mul(pow(self.exponent));
+        if zs == 0 {
+            let unit = TIME_UNITS
+                .iter()
+                .find(|(exp, _)| *exp <= self.exponent)
+                .map_or("s", |u| u.1);
+            return format!("0 {unit}");
+        }
+        let &(exp, unit) = TIME_UNITS
+            .iter()
+            .find(|(exp, _)| zs.abs() >= pow(*exp))
+            .unwrap_or(&TIME_UNITS[TIME_UNITS.len() - 1]);
+        let div = pow(exp);
+        let whole = zs / div;
+        let frac = format!("{:0width$}", (zs % div).abs(), width = (exp + 21) as usize);
+        let frac = frac[..frac.len().min(3)].trim_end_matches('0');
+        if frac.is_empty() {
+            format!("{whole} {unit}")
+        } else {
+            format!("{whole}.{frac} {unit}")
+        }
+    }
+}
+
+// written the way a VCD `$timescale` is, i.e. `10ns`
+impl std::fmt::Display for Timescale {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // exponents between units are made up with the factor
+        let &(exponent, unit) = TIME_UNITS
+            .iter()
+            .find(|u| u.0 <= self.exponent)
+            .unwrap_or(&TIME_UNITS[TIME_UNITS.len() - 1]);
+        let factor = self.factor as u64 * 10u64.pow((self.exponent - exponent).max(0) as u32);
+        write!(f, "{factor}{unit}")
+    }
+}
+
+/// Parse a timescale as written in a VCD header, i.e. `1ns` or `10 ps`.
+impl std::str::FromStr for Timescale {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, ()> {
+        let s = s.trim();
+        let split = s.find(|c: char| !c.is_ascii_digit()).ok_or(())?;
+        let factor = s[..split].parse().map_err(|_| ())?;
+        let unit = s[split..].trim();
+        let &(exponent, _) = TIME_UNITS.iter().find(|u| u.1 == unit).ok_or(())?;
+        Ok(Timescale { factor, exponent })
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ScopedVar {
+    pub scopes: Vec<(ScopeType, String)>,
+    pub var: Var,
+}
+
+// The header is parsed by hand rather than with `vcd::Parser::parse_header` because the vcd crate
+// rejects any var or scope type that isn't in the original Verilog spec (`logic`, `bit`,
+// `vhdl_architecture`, ...), which is most of what modern simulators write. Value changes are still
+// left to `vcd::Parser`.
+struct HeaderReader<'a, R> {
+    reader: &'a mut Counted<R>,
+    line: u64,
+    // the last token read and where it started, errors are about this token
+    token: Option<String>,
+    token_line: u64,
+    token_offset: u64,
+}
+
+impl<'a, R: io::BufRead> HeaderReader<'a, R> {
+    fn parse_error(&self, msg: impl std::fmt::Display) -> ParseError {
+        ParseError {
+            line: self.token_line,
+            offset: self.token_offset,
+            token: self.token.clone(),
+            msg: msg.to_string(),
+        }
+    }
+
+    fn error(&self, msg: impl std::fmt::Display) -> io::Error {
+        self.parse_error(msg).into()
+    }
+
+    fn token(&mut self) -> io::Result<Option<String>> {
+        let mut tok = vec![];
+        loop {
+            let pos = self.reader.pos;
+            let buf = self.reader.fill_buf()?;
+            if buf.is_empty() {
+                break;
             }
-            Err(err) => {
-                if let Some(err) = err.get_ref() {
-                    if let Some(err) = err.downcast_ref::<vcd::ParseError>() {
-                        log::warn!("vcd parse error: {}", err);
-                        parser.reader().read_line(&mut String::new())?;
+            let mut used = 0;
+            let mut done = false;
+            for &b in buf {
+                if b.is_ascii_whitespace() {
+                    if !tok.is_empty() {
+                        done = true;
+                        break;
+                    }
+                    if b == b'\n' {
+                        self.line += 1;
                     }
                 } else {
-                    return Err(err);
+                    if tok.is_empty() {
+                        self.token_line = self.line;
+                        self.token_offset = pos + used as u64;
+                    }
+                    tok.push(b);
+                }
+                used += 1;
+            }
+            self.reader.consume(used);
+            if done {
+                break;
+            }
+        }
+        if tok.is_empty() {
+            return Ok(None);
+        }
+        match String::from_utf8(tok) {
+            Ok(tok) => {
+                self.token = Some(tok.clone());
+                Ok(Some(tok))
+            }
+            Err(_) => {
+                self.token = None;
+                Err(self.error("invalid utf-8"))
+            }
+        }
+    }
+
+    fn expect_token(&mut self) -> io::Result<String> {
+        self.token()?.ok_or_else(|| {
+            let err = ParseError {
+                line: self.line,
+                offset: self.reader.pos,
+                token: None,
+                msg: "unexpected end of VCD file before $enddefinitions".to_owned(),
+            };
+            io::Error::new(io::ErrorKind::UnexpectedEof, err)
+        })
+    }
+
+    fn expect_end(&mut self) -> io::Result<()> {
+        let tok = self.expect_token()?;
+        if tok != "$end" {
+            return Err(self.error(format!("expected $end, found {tok:?}")));
+        }
+        Ok(())
+    }
+
+    /// Read the tokens up to the next `$end`.
+    fn skip_command(&mut self) -> io::Result<Vec<String>> {
+        let mut toks = vec![];
+        loop {
+            let tok = self.expect_token()?;
+            if tok == "$end" {
+                return Ok(toks);
+            }
+            toks.push(tok);
+        }
+    }
+
+    fn var(&mut self, scopes: &[(ScopeType, String)]) -> io::Result<ScopedVar> {
+        let var_type = self.expect_token()?;
+        let var_type = var_type
+            .parse()
+            .map_err(|_| self.error(format!("invalid var type {var_type:?}")))?;
+        let size = self.expect_token()?;
+        let size = size
+            .parse()
+            .map_err(|_| self.error(format!("invalid var size {size:?}")))?;
+        let code = self.expect_token()?;
+        let code = code
+            .parse()
+            .map_err(|_| self.error(format!("invalid id code {code:?}")))?;
+        let reference = self.expect_token()?;
+        let mut index = None;
+        let mut tok = self.expect_token()?;
+        if tok.starts_with('[') {
+            // the index is allowed to have spaces in it, i.e. `[ 7 : 0 ]`
+            let mut index_str = tok;
+            while !index_str.ends_with(']') {
+                let part = self.expect_token()?;
+                if part == "$end" {
+                    return Err(self.error(format!("unterminated var index {index_str:?}")));
                 }
+                index_str.push_str(&part);
             }
-            _ => (),
+            index = Some(
+                index_str
+                    .parse()
+                    .map_err(|_| self.error(format!("invalid var index {index_str:?}")))?,
+            );
+            tok = self.expect_token()?;
+        }
+        if tok != "$end" {
+            return Err(self.error(format!(
+                "expected $end after var {reference}, found {tok:?}"
+            )));
         }
+        Ok(ScopedVar {
+            scopes: scopes.to_vec(),
+            var: Var {
+                var_type,
+                size,
+                code,
+                reference,
+                index,
+            },
+        })
     }
+}
+
+/// Parse the header up to and including `$enddefinitions`, returning every declared variable, the
+/// timescale and the line the header ends on.
+fn parse_header<R: io::BufRead>(
+    r: &mut Counted<R>,
+    warnings: &mut Vec<ParseError>,
+) -> io::Result<(Vec<ScopedVar>, Option<Timescale>, u64)> {
+    let mut reader = HeaderReader {
+        reader: r,
+        line: 1,
+        token: None,
+        token_line: 1,
+        token_offset: 0,
+    };
+    let mut vars = vec![];
+    let mut timescale = None;
+    let mut scopes: Vec<(ScopeType, String)> = vec![];
 
-    let mut vec_output = vec![];
-    for (id, var) in id_map {
-        let mut signal = signal_map.swap_remove(&id).unwrap();
-        if let Some((_, &last_v)) = signal.ix.iter().next_back() {
-            signal.ix.insert(time, last_v);
+    loop {
+        let cmd = reader.expect_token()?;
+        match cmd.as_str() {
+            "$enddefinitions" => {
+                reader.expect_end()?;
+                break;
+            }
+            "$scope" => {
+                let scope_type = reader.expect_token()?;
+                let scope_type = scope_type
+                    .parse()
+                    .map_err(|_| reader.error(format!("invalid scope type {scope_type:?}")))?;
+                let name = reader.expect_token()?;
+                reader.expect_end()?;
+                scopes.push((scope_type, name));
+            }
+            "$upscope" => {
+                reader.expect_end()?;
+                if scopes.pop().is_none() {
+                    return Err(reader.error("$upscope without matching $scope"));
+                }
+            }
+            "$var" => vars.push(reader.var(&scopes)?),
+            "$timescale" => {
+                let toks = reader.skip_command()?.concat();
+                match toks.parse() {
+                    Ok(t) => timescale = Some(t),
+                    Err(()) => add_warning(
+                        warnings,
+                        reader.parse_error(format!("ignoring invalid timescale {toks:?}")),
+                    ),
+                }
+            }
+            "$comment" | "$date" | "$version" => {
+                reader.skip_command()?;
+            }
+            _ if cmd.starts_with('$') => {
+                // gtkwave's $attrbegin and friends
+                add_warning(
+                    warnings,
+                    reader.parse_error(format!("skipping unknown header command {cmd}")),
+                );
+                reader.skip_command()?;
+            }
+            _ => return Err(reader.error(format!("unexpected token {cmd:?} in header"))),
         }
-        vec_output.push((var, signal));
     }
 
-    Ok((vec_output, time))
+    Ok((vars, timescale, reader.line))
+}
+
+/// Make an empty signal for a var, reals and strings get their own kind of signal.
+fn new_signal(var: &Var) -> Signal {
+    if var.var_type.is_real() {
+        Signal::new_real()
+    } else if var.var_type == VarType::String {
+        Signal::new_string()
+    } else {
+        Signal::new(var.size as usize)
+    }
+}
+
+/// The state of reading the value changes after the header, kept between reads when following a
+/// file that's still being written.
+///
+/// The dump commands are blocks of value changes. `$dumpoff` sets every var to x which we don't
+/// store, the time until the next `$dumpon` is kept as not recorded instead. `$dumpvars`,
+/// `$dumpall` and `$dumpon` give the current value of every var so only actual changes are kept.
+#[derive(Default)]
+struct Body {
+    time: u64,
+    block: Option<vcd::SimulationCommand>,
+    dump_off_start: Option<u64>,
+    not_recorded: Vec<std::ops::Range<u64>>,
+    // the line the body starts on
+    first_line: u64,
+    // made while indexing a file
+    checkpoints: Option<Checkpoints>,
+    // the offset to stop reading at, after the timestamp of a checkpoint
+    end: Option<u64>,
+}
+
+// how far apart checkpoints are in the file
+const CHECKPOINT_BYTES: u64 = 8 << 20;
+
+/// Places in the body of a VCD file to start reading from, so reading a few signals can skip the
+/// parts of the file where they don't change.
+#[derive(Debug)]
+struct Checkpoints {
+    // a number for each var code, its bit in `Checkpoint::changed`
+    codes: HashMap<vcd::IdCode, usize>,
+    list: Vec<Checkpoint>,
+    // the bytes between checkpoints
+    every: u64,
+}
+
+/// A timestamp outside any dump block, the first being the start of the body.
+#[derive(Debug)]
+struct Checkpoint {
+    time: u64,
+    /// The byte offset just after the timestamp.
+    offset: u64,
+    // a bit for each var code, set if it changes before the next checkpoint
+    changed: Vec<u64>,
+}
+
+impl Checkpoints {
+    fn new(vars: &[ScopedVar], body_offset: u64, every: u64) -> Checkpoints {
+        let mut codes = HashMap::new();
+        for var in vars {
+            let n = codes.len();
+            codes.entry(var.var.code).or_insert(n);
+        }
+        let mut checkpoints = Checkpoints {
+            codes,
+            list: vec![],
+            every,
+        };
+        checkpoints.push(0, body_offset);
+        checkpoints
+    }
+
+    fn push(&mut self, time: u64, offset: u64) {
+        self.list.push(Checkpoint {
+            time,
+            offset,
+            changed: vec![0; self.codes.len().div_ceil(64)],
+        });
+    }
+
+    fn timestamp(&mut self, time: u64, offset: u64) {
+        if self
+            .list
+            .last()
+            .is_some_and(|c| offset >= c.offset + self.every)
+        {
+            self.push(time, offset);
+        }
+    }
+
+    fn changed(&mut self, code: &vcd::IdCode) {
+        if let (Some(&ix), Some(checkpoint)) = (self.codes.get(code), self.list.last_mut()) {
+            checkpoint.changed[ix / 64] |= 1 << (ix % 64);
+        }
+    }
+
+    /// The parts of the file with changes of `codes`, as the checkpoint to start reading from and
+    /// the offset to stop at (`None` for the end of the file). Neighbouring parts are read in one
+    /// go.
+    fn runs(&self, codes: &HashSet<&vcd::IdCode>) -> Vec<(&Checkpoint, Option<u64>)> {
+        let ixs: Vec<usize> = codes
+            .iter()
+            .filter_map(|c| self.codes.get(c))
+            .copied()
+            .collect();
+        let needed = |c: &Checkpoint| {
+            ixs.iter()
+                .any(|&ix| c.changed[ix / 64] >> (ix % 64) & 1 == 1)
+        };
+        let mut runs = vec![];
+        let mut start = None;
+        for (i, checkpoint) in self.list.iter().enumerate() {
+            match (start, needed(checkpoint)) {
+                (None, true) => start = Some(i),
+                (Some(s), false) => {
+                    runs.push((&self.list[s], Some(checkpoint.offset)));
+                    start = None;
+                }
+                _ => (),
+            }
+        }
+        if let Some(s) = start {
+            runs.push((&self.list[s], None));
+        }
+        runs
+    }
+}
+
+impl Body {
+    fn new(first_line: u64) -> Body {
+        Body {
+            first_line,
+            ..Body::default()
+        }
+    }
+
+    /// Read the value changes the parser has into the signals of `signal_map`. Changes for codes
+    /// not in the map are skipped, with a warning if `warn_missing` is set.
+    fn read<R: io::BufRead>(
+        &mut self,
+        parser: &mut vcd::Parser<Counted<R>>,
+        signal_map: &mut IndexMap<vcd::IdCode, Signal>,
+        warn_missing: bool,
+        warnings: &mut Vec<ParseError>,
+    ) -> io::Result<()> {
+        let first_line = self.first_line;
+        while let Some(command) = parser.next() {
+            use vcd::Command::*;
+            use vcd::SimulationCommand::*;
+            let checkpoint = matches!(self.block, Some(Dumpvars | Dumpall | Dumpon));
+            let time = self.time;
+            // the parser's lines start from the body
+            let line = first_line + parser.line() - 1;
+            let offset = parser.reader().pos;
+            let mut warn = |line: u64, id: Option<vcd::IdCode>, msg: &str| {
+                let err = ParseError {
+                    line,
+                    offset,
+                    token: id.map(|id| id.to_string()),
+                    msg: msg.to_owned(),
+                };
+                add_warning(warnings, err);
+            };
+            if let (
+                Some(checkpoints),
+                Ok(ChangeScalar(i, _) | ChangeVector(i, _) | ChangeReal(i, _) | ChangeString(i, _)),
+            ) = (&mut self.checkpoints, &command)
+            {
+                checkpoints.changed(i);
+            }
+            match command {
+                Ok(Timestamp(t)) => {
+                    self.time = t;
+                    if let (Some(checkpoints), None) = (&mut self.checkpoints, self.block) {
+                        checkpoints.timestamp(t, offset);
+                    }
+                }
+                Ok(Begin(cmd)) => {
+                    match cmd {
+                        Dumpoff if self.dump_off_start.is_none() => {
+                            self.dump_off_start = Some(time)
+                        }
+                        Dumpon => {
+                            if let Some(start) = self.dump_off_start.take() {
+                                self.not_recorded.push(start..time);
+                            }
+                        }
+                        _ => (),
+                    }
+                    self.block = Some(cmd);
+                }
+                Ok(End(_)) => self.block = None,
+                Ok(ChangeScalar(..) | ChangeVector(..) | ChangeReal(..) | ChangeString(..))
+                    if self.block == Some(Dumpoff) => {}
+                Ok(ChangeScalar(i, v)) => match signal_map.get_mut(&i) {
+                    Some(signal) if signal.is_real() || signal.is_string() => {
+                        warn(line, Some(i), "scalar change of a real or string var")
+                    }
+                    Some(signal) if checkpoint && signal.ends_with(&[v.into()]) => (),
+                    Some(signal) => signal.insert(time, vec![v.into()]),
+                    None if warn_missing => warn(line, Some(i), "change of an undeclared id code"),
+                    None => (),
+                },
+                Ok(ChangeVector(i, v)) => {
+                    // panic!("can't change vector yet");
+                    if let Some(signal) = signal_map.get_mut(&i) {
+                        let mut v: Vec<Value> = v.iter().map(|x| x.into()).collect();
+                        if signal.is_real() || signal.is_string() {
+                            warn(line, Some(i), "vector change of a real or string var");
+                        } else {
+                            if keep_low_bits(&mut v, signal.width()) {
+                                warn(line, Some(i), "value wider than its var");
+                            }
+                            if !(checkpoint && signal.ends_with(&v)) {
+                                signal.insert(time, v);
+                            }
+                        }
+                    } else if warn_missing {
+                        warn(line, Some(i), "change of an undeclared id code");
+                    }
+                }
+                Ok(ChangeReal(i, v)) => match signal_map.get_mut(&i) {
+                    Some(signal) if checkpoint && signal.ends_with_real(v) => (),
+                    Some(signal) if signal.is_real() => signal.insert_real(time, v),
+                    Some(_) => warn(line, Some(i), "real change of a var that isn't real"),
+                    None if warn_missing => warn(line, Some(i), "change of an undeclared id code"),
+                    None => (),
+                },
+                Ok(ChangeString(i, v)) => match signal_map.get_mut(&i) {
+                    Some(signal) if checkpoint && signal.ends_with_string(&v) => (),
+                    Some(signal) if signal.is_string() => signal.insert_string(time, &v),
+                    Some(_) => warn(line, Some(i), "string change of a var that isn't a string"),
+                    None if warn_missing => warn(line, Some(i), "change of an undeclared id code"),
+                    None => (),
+                },
+                Err(err) => match err
+                    .get_ref()
+                    .and_then(|e| e.downcast_ref::<vcd::ParseError>())
+                {
+                    Some(parse_err) => {
+                        let msg = format!("{}, skipping the rest of the line", parse_err.kind());
+                        warn(first_line + parse_err.line() - 1, None, &msg);
+                        parser.reader().read_line(&mut String::new())?;
+                    }
+                    None => return Err(err),
+                },
+                _ => (),
+            }
+            if self.end.is_some_and(|end| offset >= end) {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// The times the dump was off so far, including a `$dumpoff` that hasn't finished.
+    fn not_recorded(&self) -> Vec<std::ops::Range<u64>> {
+        let mut not_recorded = self.not_recorded.clone();
+        if let Some(start) = self.dump_off_start {
+            not_recorded.push(start..self.time);
+        }
+        not_recorded
+    }
+}
+
+/// Read a VCD file, giving every variable along with its signal and the final time.
+///
+/// Variables that share an identifier code (i.e. a port and the net driving it) share the same
+/// signal.
+pub fn read_clocked_vcd(r: &mut impl io::BufRead) -> std::io::Result<Waveform> {
+    let mut parser = vcd::Parser::new(Counted { inner: r, pos: 0 });
+
+    // The VCD spec is weird and confusing. There's a couple of features I'm not bothering to
+    // impliment yet (and probably others I've missed or misunderstood):
+    //
+    //   - the reference indexes are ignored, I don't really understand why you'd want it and it's
+    //     annoying the resolve the types (but also shouldn't be that difficult)
+    //
+    // I assume that time isn't allowed to go backwards but I don't think this is explicit in the
+    // spec. This implimentation allows going backwards in time only to change signals whose values
+    // for a later time haven't yet been written. i.e. each individual signal needs monotonous times
+    // but this can be interleaved in the vcd files.
+
+    // Parse the header and find the wires
+    let mut warnings = vec![];
+    let (vars, timescale, header_lines) = parse_header(parser.reader(), &mut warnings)?;
+    // the first var declared with a code decides the type of the signal
+    let mut signal_map: IndexMap<vcd::IdCode, Signal> = IndexMap::new();
+    for item in &vars {
+        signal_map
+            .entry(item.var.code)
+            .or_insert_with(|| new_signal(&item.var));
+    }
+    let mut body = Body::new(header_lines);
+    body.read(&mut parser, &mut signal_map, true, &mut warnings)?;
+    let time = body.time;
+    let not_recorded = body.not_recorded();
+
+    let signal_map: IndexMap<vcd::IdCode, Arc<Signal>> = signal_map
+        .into_iter()
+        .map(|(id, mut signal)| {
+            signal.extend_to(time);
+            (id, Arc::new(signal))
+        })
+        .collect();
+    let vars = vars
+        .into_iter()
+        .map(|var| {
+            let signal = Arc::clone(&signal_map[&var.var.code]);
+            (var, signal)
+        })
+        .collect();
+
+    Ok(Waveform {
+        vars,
+        final_time: time,
+        timescale,
+        not_recorded,
+        warnings,
+    })
+}
+
+/// The header of a VCD file and checkpoints through its value changes, made with [`index_vcd`].
+/// Signals are read from the file when they're needed with [`VcdIndex::read_signals`].
+#[derive(Debug)]
+pub struct VcdIndex {
+    pub vars: Vec<ScopedVar>,
+    pub final_time: u64,
+    pub timescale: Option<Timescale>,
+    pub not_recorded: Vec<std::ops::Range<u64>>,
+    pub warnings: Vec<ParseError>,
+    checkpoints: Checkpoints,
+}
+
+/// Read the header of a VCD file and skim the value changes for the final time and dump periods
+/// without keeping any of the values. Every few MB it notes where a timestamp is and which vars
+/// change before the next one so signals can be read from just the parts of the file they're in.
+pub fn index_vcd(r: &mut impl io::BufRead) -> io::Result<VcdIndex> {
+    index_vcd_every(r, CHECKPOINT_BYTES)
+}
+
+fn index_vcd_every(r: &mut impl io::BufRead, every: u64) -> io::Result<VcdIndex> {
+    let mut parser = vcd::Parser::new(Counted { inner: r, pos: 0 });
+    let mut warnings = vec![];
+    let (vars, timescale, body_line) = parse_header(parser.reader(), &mut warnings)?;
+    let body_offset = parser.reader().pos;
+    let mut body = Body::new(body_line);
+    body.checkpoints = Some(Checkpoints::new(&vars, body_offset, every));
+    body.read(&mut parser, &mut IndexMap::new(), false, &mut warnings)?;
+    Ok(VcdIndex {
+        vars,
+        final_time: body.time,
+        timescale,
+        not_recorded: body.not_recorded(),
+        warnings,
+        checkpoints: body.checkpoints.take().unwrap(),
+    })
+}
+
+impl VcdIndex {
+    /// Read the signals for `codes` from the file the index was made from, skipping the parts of
+    /// the file where none of them change. Codes that aren't declared in the header are left out.
+    pub fn read_signals(
+        &self,
+        r: &mut (impl io::BufRead + io::Seek),
+        codes: &[vcd::IdCode],
+    ) -> io::Result<Vec<(vcd::IdCode, Signal)>> {
+        let codes: HashSet<&vcd::IdCode> = codes.iter().collect();
+        let mut signal_map: IndexMap<vcd::IdCode, Signal> = IndexMap::new();
+        for item in &self.vars {
+            if codes.contains(&item.var.code) {
+                signal_map
+                    .entry(item.var.code)
+                    .or_insert_with(|| new_signal(&item.var));
+            }
+        }
+        for (checkpoint, end) in self.checkpoints.runs(&codes) {
+            r.seek(io::SeekFrom::Start(checkpoint.offset))?;
+            let mut parser = vcd::Parser::new(Counted {
+                inner: &mut *r,
+                pos: checkpoint.offset,
+            });
+            let mut body = Body {
+                time: checkpoint.time,
+                end,
+                ..Body::default()
+            };
+            // the warnings were already found making the index
+            body.read(&mut parser, &mut signal_map, false, &mut vec![])?;
+        }
+        Ok(signal_map
+            .into_iter()
+            .map(|(id, mut signal)| {
+                signal.extend_to(self.final_time);
+                (id, signal)
+            })
+            .collect())
+    }
+}
+
+/// Reads a VCD file that's still being written, i.e. by a running simulation. The bytes are given
+/// as they're appended with [`VcdFollower::feed`] and the signals grow in place, keeping the
+/// parser's state between feeds.
+pub struct VcdFollower {
+    // bytes the parser hasn't been given yet, the whole header until it's complete then the end of
+    // an unfinished line
+    pending: Vec<u8>,
+    body: Option<FollowedBody>,
+    pub warnings: Vec<ParseError>,
+}
+
+struct FollowedBody {
+    vars: Vec<ScopedVar>,
+    timescale: Option<Timescale>,
+    parser: vcd::Parser<Counted<io::Cursor<Vec<u8>>>>,
+    body: Body,
+    signals: IndexMap<vcd::IdCode, Arc<Signal>>,
+}
+
+impl Default for VcdFollower {
+    fn default() -> Self {
+        VcdFollower::new()
+    }
+}
+
+impl VcdFollower {
+    pub fn new() -> VcdFollower {
+        VcdFollower {
+            pending: vec![],
+            body: None,
+            warnings: vec![],
+        }
+    }
+
+    /// Read the bytes appended to the file since the last feed.
+    ///
+    /// Only whole lines are parsed so a token split between two feeds is never seen in halves. The
+    /// signals are changed in place when nothing else holds them, otherwise they're copied first.
+    pub fn feed(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.pending.extend_from_slice(bytes);
+        let Some(end) = self.pending.iter().rposition(|&b| b == b'\n') else {
+            return Ok(());
+        };
+        let lines: Vec<u8> = match &mut self.body {
+            Some(_) => self.pending.drain(..=end).collect(),
+            None => {
+                // the header is parsed from the start each time until it's all there
+                let mut r = Counted {
+                    inner: &self.pending[..=end],
+                    pos: 0,
+                };
+                let mut warnings = vec![];
+                let (vars, timescale, line) = match parse_header(&mut r, &mut warnings) {
+                    Ok(header) => header,
+                    Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+                    Err(err) => return Err(err),
+                };
+                let pos = r.pos;
+                let mut signals = IndexMap::new();
+                for item in &vars {
+                    signals
+                        .entry(item.var.code)
+                        .or_insert_with(|| Arc::new(new_signal(&item.var)));
+                }
+                self.warnings = warnings;
+                self.body = Some(FollowedBody {
+                    vars,
+                    timescale,
+                    parser: vcd::Parser::new(Counted {
+                        inner: io::Cursor::new(vec![]),
+                        pos,
+                    }),
+                    body: Body::new(line),
+                    signals,
+                });
+                self.pending.drain(..pos as usize);
+                let end = self.pending.iter().rposition(|&b| b == b'\n');
+                self.pending.drain(..end.map_or(0, |end| end + 1)).collect()
+            }
+        };
+        let Some(followed) = &mut self.body else {
+            return Ok(());
+        };
+
+        // the cursor only keeps what the parser hasn't read
+        let cursor = &mut followed.parser.reader().inner;
+        let read = cursor.position() as usize;
+        cursor.get_mut().drain(..read);
+        cursor.get_mut().extend_from_slice(&lines);
+        cursor.set_position(0);
+
+        let mut signal_map: IndexMap<vcd::IdCode, Signal> = std::mem::take(&mut followed.signals)
+            .into_iter()
+            .map(|(id, signal)| {
+                let mut signal = Arc::try_unwrap(signal).unwrap_or_else(|s| (*s).clone());
+                signal.unextend();
+                (id, signal)
+            })
+            .collect();
+        let result = followed.body.read(
+            &mut followed.parser,
+            &mut signal_map,
+            true,
+            &mut self.warnings,
+        );
+        let time = followed.body.time;
+        followed.signals = signal_map
+            .into_iter()
+            .map(|(id, mut signal)| {
+                signal.extend_to(time);
+                (id, Arc::new(signal))
+            })
+            .collect();
+        result
+    }
+
+    /// The waveform read so far, once the header is complete. The signals are shared with the
+    /// follower so should be dropped before the next feed to avoid copying them.
+    pub fn waveform(&self) -> Option<Waveform> {
+        let followed = self.body.as_ref()?;
+        Some(Waveform {
+            vars: followed
+                .vars
+                .iter()
+                .map(|var| (var.clone(), Arc::clone(&followed.signals[&var.var.code])))
+                .collect(),
+            final_time: followed.body.time,
+            timescale: followed.timescale,
+            not_recorded: followed.body.not_recorded(),
+            warnings: self.warnings.clone(),
+        })
+    }
+
+    /// Whether the header has been read.
+    pub fn has_header(&self) -> bool {
+        self.body.is_some()
+    }
+
+    pub fn final_time(&self) -> u64 {
+        self.body.as_ref().map_or(0, |followed| followed.body.time)
+    }
+
+    /// The times the dump was off, including one that's still going.
+    pub fn not_recorded(&self) -> Vec<std::ops::Range<u64>> {
+        self.body
+            .as_ref()
+            .map_or(vec![], |followed| followed.body.not_recorded())
+    }
+
+    /// The signal for an id code declared in the header.
+    pub fn signal(&self, code: &vcd::IdCode) -> Option<&Arc<Signal>> {
+        self.body.as_ref()?.signals.get(code)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const ALL_TYPES: &str = "$timescale 1ns $end
+$scope module top $end
+$var wire 1 ! clk $end
+$var logic 8 \" data [7:0] $end
+$var bit 1 # flag $end
+$var integer 32 $ count $end
+$var parameter 4 % WIDTH $end
+$scope vhdl_architecture inner $end
+$var real 64 & volts $end
+$upscope $end
+$var string 1 ' state $end
+$upscope $end
+$enddefinitions $end
+#0
+$dumpvars
+0!
+b00000000 \"
+1#
+b0 $
+b1000 %
+r0.5 &
+sIDLE '
+$end
+#5
+1!
+b10100101 \"
+sBUSY '
+#10
+0!
+";
+
+    #[test]
+    fn test_var_types() {
+        let wave = read_clocked_vcd(&mut ALL_TYPES.as_bytes()).unwrap();
+        assert_eq!(wave.final_time, 10);
+        assert_eq!(
+            wave.timescale,
+            Some(Timescale {
+                factor: 1,
+                exponent: -9
+            })
+        );
+        let vars = wave.vars;
+        let types: Vec<_> = vars.iter().map(|(v, _)| v.var.var_type).collect();
+        assert_eq!(
+            types,
+            [
+                VarType::Wire,
+                VarType::Logic,
+                VarType::Bit,
+                VarType::Integer,
+                VarType::Parameter,
+                VarType::Real,
+                VarType::String,
+            ]
+        );
+        let (data, data_sig) = &vars[1];
+        assert_eq!(data.var.reference, "data");
+        assert!(matches!(
+            data.var.index,
+            Some(vcd::ReferenceIndex::Range(7, 0))
+        ));
+        assert_eq!(data_sig.width(), 8);
+        assert_eq!(
+            vars[5].0.scopes,
+            [
+                (ScopeType::Module, "top".to_owned()),
+                (ScopeType::VhdlArchitecture, "inner".to_owned())
+            ]
+        );
+
+        // a stray token is an error where it is rather than the start of an index
+        let vcd = "$var wire 1 ! a b $end
+$var wire 1 \" c $end
+$enddefinitions $end
+";
+        let err = read_clocked_vcd(&mut vcd.as_bytes()).unwrap_err();
+        let err = ParseError::from_io(&err).unwrap();
+        assert_eq!((err.line, err.token.as_deref()), (1, Some("b")));
+        let vcd = "$var wire 8 ! a [ 7 : 0 ] $end
+$enddefinitions $end
+";
+        let wave = read_clocked_vcd(&mut vcd.as_bytes()).unwrap();
+        assert!(matches!(
+            wave.vars[0].0.var.index,
+            Some(vcd::ReferenceIndex::Range(7, 0))
+        ));
+    }
+
+    #[test]
+    fn test_real() {
+        let vars = read_clocked_vcd(&mut ALL_TYPES.as_bytes()).unwrap().vars;
+        let volts = &vars[5].1;
+        assert!(volts.is_real());
+        let values: Vec<_> = volts.real_range(0..10).into_iter().collect();
+        assert_eq!(values, [(0, 0.5), (10, 0.5)]);
+    }
+
+    #[test]
+    fn test_string() {
+        let vars = read_clocked_vcd(&mut ALL_TYPES.as_bytes()).unwrap().vars;
+        let state = &vars[6].1;
+        assert!(state.is_string());
+        let values: Vec<_> = state.string_range(0..10).into_iter().collect();
+        assert_eq!(values, [(0, "IDLE"), (5, "BUSY"), (10, "BUSY")]);
+    }
+
+    #[test]
+    fn test_aliases() {
+        let vcd = "$scope module top $end
+$var wire 1 ! clk $end
+$scope module sub $end
+$var wire 1 ! clk_in $end
+$upscope $end
+$upscope $end
+$enddefinitions $end
+#0
+0!
+#5
+1!
+";
+        let vars = read_clocked_vcd(&mut vcd.as_bytes()).unwrap().vars;
+        assert_eq!(vars.len(), 2);
+        assert_eq!(vars[1].0.var.reference, "clk_in");
+        assert_eq!(vars[1].0.scopes.len(), 2);
+        assert!(Arc::ptr_eq(&vars[0].1, &vars[1].1));
+        let values: Vec<_> = vars[1].1.bit_range(0..5).into_iter().collect();
+        assert_eq!(values, [(0, Value::V0), (5, Value::V1)]);
+    }
+
+    #[test]
+    fn test_timescale_format() {
+        let ps: Timescale = "1 ps".parse().unwrap();
+        assert_eq!(ps.format(0), "0 ps");
+        assert_eq!(ps.format(999), "999 ps");
+        assert_eq!(ps.format(1500), "1.5 ns");
+        assert_eq!(ps.format(-2_000_000), "-2 us");
+        let ns10: Timescale = "10ns".parse().unwrap();
+        assert_eq!(ns10.format(123), "1.23 us");
+        assert_eq!(ns10.format(1), "10 ns");
+        assert!("ns".parse::<Timescale>().is_err());
+    }
+
+    #[test]
+    fn test_dump_off() {
+        let vcd = "$scope module top $end
+$var wire 1 ! clk $end
+$var wire 4 \" count $end
+$upscope $end
+$enddefinitions $end
+#0
+$dumpvars
+0!
+b0001 \"
+$end
+#5
+1!
+#10
+$dumpoff
+x!
+bxxxx \"
+$end
+#20
+$dumpon
+1!
+b0010 \"
+$end
+#25
+$dumpall
+1!
+b0010 \"
+$end
+#30
+0!
+";
+        let wave = read_clocked_vcd(&mut vcd.as_bytes()).unwrap();
+        assert_eq!(wave.not_recorded.len(), 1);
+        assert_eq!(wave.not_recorded[0], 10..20);
+        let clk: Vec<_> = wave.vars[0].1.bit_range(0..30).into_iter().collect();
+        assert_eq!(clk, [(0, Value::V0), (5, Value::V1), (30, Value::V0)]);
+        let count: Vec<_> = wave.vars[1]
+            .1
+            .range(0..30)
+            .into_iter()
+            .map(|x| x.0)
+            .collect();
+        assert_eq!(count, [0, 20, 30]);
+    }
+
+    #[test]
+    fn test_index() {
+        let vcd = "$timescale 1ns $end
+$scope module top $end
+$var wire 1 ! clk $end
+$var wire 4 \" count $end
+$var wire 1 ! clk_alias $end
+$upscope $end
+$enddefinitions $end
+#0
+0!
+b0001 \"
+#10
+$dumpoff
+x!
+bxxxx \"
+$end
+#20
+$dumpon
+1!
+b0010 \"
+$end
+#30
+0!
+";
+        let mut r = io::Cursor::new(vcd);
+        let index = index_vcd(&mut r).unwrap();
+        assert_eq!(index.vars.len(), 3);
+        assert_eq!(index.final_time, 30);
+        assert_eq!(index.not_recorded.len(), 1);
+        assert_eq!(index.not_recorded[0], 10..20);
+        assert_eq!(index.timescale, Some("1ns".parse().unwrap()));
+
+        let code = index.vars[2].var.code;
+        let signals = index.read_signals(&mut r, &[code]).unwrap();
+        assert_eq!(signals.len(), 1);
+        assert_eq!(signals[0].0, code);
+        let clk: Vec<_> = signals[0].1.bit_range(0..30).into_iter().collect();
+        assert_eq!(clk, [(0, Value::V0), (20, Value::V1), (30, Value::V0)]);
+
+        // with a checkpoint at every timestamp, reading count stops before the last one where only
+        // clk changes
+        let index = index_vcd_every(&mut io::Cursor::new(vcd), 1).unwrap();
+        let count = index.vars[1].var.code;
+        let runs = index.checkpoints.runs(&[&count].into_iter().collect());
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].0.time, 0);
+        assert_eq!(runs[0].1, Some(vcd.find("#30").unwrap() as u64 + 4));
+        let signals = index.read_signals(&mut r, &[count, code]).unwrap();
+        let count: Vec<_> = signals[1].1.range(0..30).into_iter().map(|c| c.0).collect();
+        assert_eq!(count, [0, 20, 30]);
+        let clk: Vec<_> = signals[0].1.bit_range(0..30).into_iter().collect();
+        assert_eq!(clk, [(0, Value::V0), (20, Value::V1), (30, Value::V0)]);
+    }
+
+    #[test]
+    fn test_packed_values() {
+        use Value::*;
+        let mut signal = Signal::new(3);
+        signal.insert(0, vec![V1, V0, V1]);
+        signal.insert(10, vec![V1]);
+        assert!(!signal.bits().four_state);
+        // an x switches to two bits per value without losing the earlier values
+        signal.insert(20, vec![X, Z, V1]);
+        assert!(signal.bits().four_state);
+        // going back in time and replacing a change
+        signal.insert(5, vec![V0, V0, V0]);
+        signal.insert(20, vec![Z, Z, Z]);
+        // short values are extended on the left, with x or z when that's the top bit
+        signal.insert(25, vec![X, V1]);
+        let changes: Vec<_> = signal
+            .range(0..30)
+            .into_iter()
+            .map(|(t, v)| (t, v.to_vec()))
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                (0, vec![V1, V0, V1]),
+                (5, vec![V0, V0, V0]),
+                (10, vec![V0, V0, V1]),
+                (20, vec![Z, Z, Z]),
+                (25, vec![X, X, V1]),
+            ]
+        );
+        assert!(signal.ends_with(&[X, V1]));
+        let changes: Vec<_> = signal.range(6..10).into_iter().map(|x| x.0).collect();
+        assert_eq!(changes, [5, 10]);
+        let bits: Vec<_> = signal.bit_range(0..30).into_iter().collect();
+        assert_eq!(bits, [(0, V1), (5, V0), (10, V0), (20, Z), (25, X)]);
+
+        let vcd = "$var wire 8 ! d $end
+$enddefinitions $end
+#0
+b1 !
+#5
+bz0 !
+";
+        let wave = read_clocked_vcd(&mut vcd.as_bytes()).unwrap();
+        let d = &wave.vars[0].1;
+        assert_eq!(d.value_at(0), Some(vec![V0, V0, V0, V0, V0, V0, V0, V1]));
+        assert_eq!(d.value_at(5), Some(vec![Z, Z, Z, Z, Z, Z, Z, V0]));
+    }
+
+    #[test]
+    fn test_queries() {
+        let empty = Signal::new(1);
+        assert_eq!(empty.bit_at(5), None);
+        assert_eq!(empty.next_change(0), None);
+        assert_eq!(empty.count_changes(0..10), 0);
+
+        let mut clk = Signal::new(1);
+        clk.insert_bit(5, Value::V0);
+        clk.insert_bit(10, Value::V1);
+        clk.insert_bit(20, Value::V0);
+        clk.extend_to(30);
+        assert_eq!(clk.bit_at(4), None);
+        assert_eq!(clk.bit_at(5), Some(Value::V0));
+        assert_eq!(clk.bit_at(9), Some(Value::V0));
+        assert_eq!(clk.bit_at(10), Some(Value::V1));
+        assert_eq!(clk.bit_at(100), Some(Value::V0));
+        assert_eq!(clk.value_at(10), Some(vec![Value::V1]));
+
+        assert_eq!(clk.prev_change(5), None);
+        assert_eq!(clk.prev_change(10), Some(5));
+        assert_eq!(clk.prev_change(11), Some(10));
+        assert_eq!(clk.next_change(0), Some(5));
+        assert_eq!(clk.next_change(10), Some(20));
+        // the end of the simulation isn't a change
+        assert_eq!(clk.next_change(20), None);
+        assert_eq!(clk.count_changes(5..20), 2);
+        assert_eq!(clk.count_changes(0..100), 3);
+        assert_eq!(clk.next_edge(0, Value::V1), Some(10));
+        assert_eq!(clk.next_edge(10, Value::V1), None);
+        assert_eq!(clk.next_edge(5, Value::V0), Some(20));
+        assert_eq!(clk.prev_edge(20, Value::V0), Some(5));
+        assert_eq!(clk.prev_edge(100, Value::V1), Some(10));
+
+        // asking the wrong kind of signal isn't an error
+        let mut name = Signal::new_string();
+        name.insert_string(0, "idle");
+        assert_eq!(name.value_at(0), None);
+        assert_eq!(name.next_edge(0, Value::V1), None);
+        assert_eq!(name.real_at(0), None);
+        assert_eq!(name.string_at(0), Some("idle"));
+        assert_eq!(clk.string_at(10), None);
+
+        let mut name = Signal::new_string();
+        name.insert_string(0, "IDLE");
+        name.insert_string(5, "BUSY");
+        assert_eq!(name.string_at(4), Some("IDLE"));
+        assert_eq!(name.string_at(5), Some("BUSY"));
+    }
+
+    #[test]
+    fn test_slice() {
+        let mut data = Signal::new(4);
+        data.insert(0, bits("0010"));
+        data.insert(5, bits("0011"));
+        data.insert(10, bits("1x11"));
+        data.extend_to(30);
+        let high = data.slice(0..2).unwrap();
+        assert_eq!(high.value_at(5), Some(bits("00")));
+        assert_eq!(high.value_at(10), Some(bits("1x")));
+        assert_eq!(high.count_changes(0..30), 2);
+        assert_eq!(high.final_time(), 30);
+        assert_eq!(data.slice(2..3).unwrap().count_changes(0..30), 1);
+        assert_eq!(data.slice(3..4).unwrap().count_changes(0..30), 2);
+
+        assert!(data.slice(2..5).is_err());
+        assert!(data.slice(2..2).is_err());
+        assert!(Signal::new_real().slice(0..1).is_err());
+    }
+
+    #[test]
+    fn test_concat() {
+        let mut clk = Signal::new(1);
+        clk.insert_bit(5, Value::V0);
+        clk.insert_bit(10, Value::V1);
+        clk.insert_bit(20, Value::V0);
+        clk.extend_to(30);
+        let mut data = Signal::new(2);
+        data.insert(0, bits("00"));
+        data.insert(10, bits("1x"));
+        data.extend_to(25);
+        // parts of any width, each x until its first change
+        let bus = Signal::concat(&[&clk, &data]).unwrap();
+        assert_eq!(bus.width(), 3);
+        assert_eq!(bus.value_at(0), Some(bits("x00")));
+        assert_eq!(bus.value_at(10), Some(bits("11x")));
+        assert_eq!(bus.count_changes(0..30), 4);
+        assert_eq!(bus.final_time(), 30);
+        let bus = Signal::concat(&[&data, &clk]).unwrap();
+        assert_eq!(bus.value_at(20), Some(bits("1x0")));
+
+        assert!(Signal::concat(&[]).is_err());
+        assert!(Signal::concat(&[&clk, &Signal::new_real()]).is_err());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let vcd = "$scope module top $end
+$var wire 1 ! clk $end
+$var wobble 1 \" bad $end
+";
+        let err = read_clocked_vcd(&mut vcd.as_bytes()).unwrap_err();
+        let err = ParseError::from_io(&err).unwrap();
+        assert_eq!(err.line, 3);
+        assert_eq!(err.offset, vcd.find("wobble").unwrap() as u64);
+        assert_eq!(err.token.as_deref(), Some("wobble"));
+
+        let vcd = "$scope module top $end
+$var wire 1 ! clk $end
+$upscope $end
+$enddefinitions $end
+#0
+0!
+1%
+#5
+1!
+";
+        let wave = read_clocked_vcd(&mut vcd.as_bytes()).unwrap();
+        assert_eq!(wave.final_time, 5);
+        assert_eq!(wave.warnings.len(), 1);
+        assert_eq!(wave.warnings[0].line, 7);
+        assert_eq!(wave.warnings[0].token.as_deref(), Some("%"));
+    }
+
+    #[test]
+    fn test_wide_values() {
+        let vcd = "$var wire 2 ! d $end
+$enddefinitions $end
+#0
+b101 !
+#5
+b10 !
+";
+        let wave = read_clocked_vcd(&mut vcd.as_bytes()).unwrap();
+        let d = &wave.vars[0].1;
+        assert_eq!(d.value_at(0), Some(vec![Value::V0, Value::V1]));
+        assert_eq!(d.value_at(5), Some(vec![Value::V1, Value::V0]));
+        assert_eq!(wave.warnings.len(), 1);
+        assert_eq!(wave.warnings[0].line, 4);
+        assert_eq!(wave.warnings[0].msg, "value wider than its var");
+    }
+
+    #[test]
+    fn test_follow() {
+        let vcd = ALL_TYPES.as_bytes();
+        let mut follower = VcdFollower::new();
+        // split in the middle of the header and of a value change
+        let header_end = ALL_TYPES.find("#0").unwrap();
+        let split = ALL_TYPES.find("b10100101").unwrap() + 3;
+        follower.feed(&vcd[..40]).unwrap();
+        assert!(!follower.has_header());
+        follower.feed(&vcd[40..header_end + 1]).unwrap();
+        assert!(follower.has_header());
+        follower.feed(&vcd[header_end + 1..split]).unwrap();
+        assert_eq!(follower.final_time(), 5);
+
+        // a signal still held keeps the values it had
+        let data = follower.waveform().unwrap().vars[1].1.clone();
+        follower.feed(&vcd[split..]).unwrap();
+        assert_eq!(data.value_at(5), Some(vec![Value::V0; 8]));
+        let wave = follower.waveform().unwrap();
+        assert_eq!(wave.final_time, 10);
+        let data = &wave.vars[1].1;
+        assert_eq!(data.value_at(5).unwrap()[0], Value::V1);
+        assert_eq!(data.final_time(), 10);
+        assert_eq!(wave.vars[0].1.value_at(10), Some(vec![Value::V0]));
+        assert!(follower.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_radix() {
+        assert_eq!(Radix::Binary.format(&bits("01x1")), "01x1");
+        assert_eq!(Radix::Hex.format(&bits("000010100101")), "a5");
+        assert_eq!(Radix::Hex.format(&bits("1zzzz0x01")), "1zx");
+        assert_eq!(Radix::Unsigned.format(&bits("11111111")), "255");
+        assert_eq!(Radix::Signed.format(&bits("11111111")), "-1");
+        assert_eq!(Radix::Signed.format(&bits("10000000")), "-128");
+        assert_eq!(Radix::Signed.format(&bits("0111")), "7");
+        assert_eq!(Radix::Unsigned.format(&bits("10x")), "x");
+        let wide = format!("1{}", "0".repeat(100));
+        assert_eq!(
+            Radix::Unsigned.format(&bits(&wide)),
+            "1267650600228229401496703205376"
+        );
+    }
 }
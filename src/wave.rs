@@ -1,14 +1,29 @@
 use eframe::egui;
 use egui::*;
 use egui_plot::PlotPoint;
-use std::ops::RangeInclusive;
+use std::ops::{Range, RangeInclusive};
 // use std::ops::RangeInclusive;
 use crate::vcd;
 
+/// How a real signal is drawn between its value changes.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub enum Interpolation {
+    /// Hold each value until the next change.
+    #[default]
+    Step,
+    /// Draw a straight line between consecutive values.
+    Linear,
+}
+
 pub struct Wave<'a> {
     scale: f32,
     view_range: RangeInclusive<f32>,
     pub height: f32,
+    pub interpolation: Interpolation,
+    /// How bus values without a label are written.
+    pub radix: vcd::Radix,
+    /// Times that weren't recorded, drawn hatched over the signal.
+    pub not_recorded: &'a [Range<u64>],
     name: &'a str,
     // wave_data: &'a [bool],
     wave_data: &'a vcd::Signal,
@@ -43,6 +58,22 @@ pub struct Wave<'a> {
 //     pts
 // }
 
+fn pos_from_val(value: PlotPoint, rect: Rect, len: usize) -> egui::Pos2 {
+    let x = remap(
+        value.x as f32,
+        // range,
+        0.0..=(len as f32),
+        rect.left()..=rect.right(),
+        // 0.0..=(32.0),
+    );
+    let y = remap(
+        value.y as f32,
+        0.0..=1.0,
+        rect.bottom()..=rect.top(), // negated y axis!
+    );
+    pos2(x, y)
+}
+
 impl<'a> Wave<'a> {
     // pub fn new(name: &'a str, scale: f32, view_range: RangeInclusive<f32>, wave_data: &'a [bool]) -> Self {
     pub fn new(
@@ -55,6 +86,9 @@ impl<'a> Wave<'a> {
             scale,
             view_range,
             height: 32.0,
+            interpolation: Interpolation::default(),
+            radix: vcd::Radix::default(),
+            not_recorded: &[],
             wave_data,
             name,
         }
@@ -65,6 +99,9 @@ impl<'a> Wave<'a> {
             scale,
             view_range,
             height,
+            interpolation,
+            radix,
+            not_recorded,
             wave_data,
             name,
         } = self;
@@ -116,7 +153,20 @@ impl<'a> Wave<'a> {
         // - undefined values should be visible
         // - the last signal at the end of the simulation should be visible (currently it gets cut
         //   off)
-        if wave_data.width() == 1 {
+        if wave_data.is_real() {
+            let values: Vec<(u64, f64)> = wave_data
+                .real_range(first_ix..last_ix)
+                .into_iter()
+                .collect();
+            wave_painter.extend(real_shapes(
+                ui,
+                rect,
+                &view_range,
+                wave_data.final_time(),
+                &values,
+                interpolation,
+            ));
+        } else if wave_data.width() == 1 {
             let mut pts = vec![];
             let mut scalars = wave_data.bit_range(first_ix..last_ix).into_iter();
             let (t0, v0) = scalars.next().unwrap();
@@ -178,22 +228,6 @@ impl<'a> Wave<'a> {
             //     pts.push(PlotPoint::new(last_view_ix as f32, 0.1));
             // }
 
-            fn pos_from_val(value: PlotPoint, rect: Rect, len: usize) -> egui::Pos2 {
-                let x = remap(
-                    value.x as f32,
-                    // range,
-                    0.0..=(len as f32),
-                    rect.left()..=rect.right(),
-                    // 0.0..=(32.0),
-                );
-                let y = remap(
-                    value.y as f32,
-                    0.0..=1.0,
-                    rect.bottom()..=rect.top(), // negated y axis!
-                );
-                pos2(x as f32, y as f32)
-            }
-
             let stroke = ui.style().visuals.widgets.active.bg_stroke;
 
             let shapes = vec![Shape::line(
@@ -203,101 +237,267 @@ impl<'a> Wave<'a> {
                 stroke,
             )];
             ui.painter().extend(shapes);
+        } else if wave_data.is_string() {
+            let changes: Vec<(u64, &str)> = wave_data
+                .string_range(first_ix..last_ix)
+                .into_iter()
+                .collect();
+            let shapes = bus_shapes(
+                ui,
+                rect,
+                scale,
+                first_ix..last_ix,
+                wave_data.final_time(),
+                &changes,
+                |s| s.to_string(),
+            );
+            ui.painter().extend(shapes);
         } else {
-            let x_taper = 0.1;
-            let mut pts_a = vec![];
-            let mut pts_b = vec![];
-
-            let mut values = wave_data.range(first_ix..last_ix).into_iter();
-            let (t0, _v0) = values.next().unwrap();
-            let mut x = t0 as f32;
-            // let mut y;
-            pts_a.push(PlotPoint::new(x, 0.5));
-            // pts_a.push(PlotPoint::new(x + 0.1, 0.1));
-            pts_b.push(PlotPoint::new(x, 0.5));
-            // pts_b.push(PlotPoint::new(x + 0.1, 0.9));
-            // let mut polarity = true;
+            let changes: Vec<(u64, vcd::PackedBits<'_>)> =
+                wave_data.range(first_ix..last_ix).into_iter().collect();
+            // the bits of the value being labelled
+            let mut bits = vec![];
+            let shapes = bus_shapes(
+                ui,
+                rect,
+                scale,
+                first_ix..last_ix,
+                wave_data.final_time(),
+                &changes,
+                |vs| {
+                    bits.clear();
+                    bits.extend(vs.iter());
+                    match wave_data.label(&bits) {
+                        Some(label) => label.to_string(),
+                        None => radix.format(&bits),
+                    }
+                },
+            );
+            ui.painter().extend(shapes);
+        }
+        wave_painter.extend(not_recorded_shapes(
+            ui,
+            rect,
+            scale,
+            first_ix..last_ix,
+            not_recorded,
+        ));
+    }
+}
 
-            for (t, _) in values {
-                pts_a.push(PlotPoint::new(x + x_taper, 0.1));
-                pts_b.push(PlotPoint::new(x + x_taper, 0.9));
-                x = t as f32;
-                pts_a.push(PlotPoint::new(x - x_taper, 0.1));
-                pts_a.push(PlotPoint::new(x, 0.5));
-                pts_b.push(PlotPoint::new(x - x_taper, 0.9));
-                pts_b.push(PlotPoint::new(x, 0.5));
+/// Hatched boxes covering the parts of `not_recorded` in `view`.
+fn not_recorded_shapes(
+    ui: &Ui,
+    rect: Rect,
+    scale: f32,
+    view: Range<u64>,
+    not_recorded: &[Range<u64>],
+) -> Vec<Shape> {
+    let x_of = |t: u64| rect.left() + t as f32 * scale * 32.0;
+    let stroke = Stroke::new(1.0, ui.visuals().weak_text_color());
+    let spacing = 8.0;
+    let height = rect.height();
+    let mut shapes = vec![];
+    for gap in not_recorded
+        .iter()
+        .filter(|gap| gap.start < view.end && gap.end > view.start)
+    {
+        let gap_left = x_of(gap.start);
+        let left = gap_left.max(x_of(view.start));
+        let right = x_of(gap.end).min(x_of(view.end));
+        shapes.push(Shape::rect_filled(
+            Rect::from_x_y_ranges(left..=right, rect.y_range()),
+            0.0,
+            ui.visuals().extreme_bg_color,
+        ));
+        // diagonal lines lined up with the start of the gap so they don't move when scrolling,
+        // each one is cut to the visible part of the gap
+        let mut x = gap_left + ((left - height - gap_left) / spacing).floor().max(0.0) * spacing;
+        while x < right {
+            let s0 = (left - x).max(0.0);
+            let s1 = (right - x).min(height);
+            if s0 < s1 {
+                shapes.push(Shape::line_segment(
+                    [
+                        pos2(x + s0, rect.bottom() - s0),
+                        pos2(x + s1, rect.bottom() - s1),
+                    ],
+                    stroke,
+                ));
             }
-            fn pos_from_val(value: PlotPoint, rect: Rect, len: usize) -> egui::Pos2 {
-                let x = remap(
-                    value.x as f32,
-                    // range,
-                    0.0..=(len as f32),
-                    rect.left()..=rect.right(),
-                    // 0.0..=(32.0),
-                );
-                let y = remap(
-                    value.y as f32,
-                    0.0..=1.0,
-                    rect.bottom()..=rect.top(), // negated y axis!
-                );
-                pos2(x as f32, y as f32)
+            x += spacing;
+        }
+    }
+    shapes
+}
+
+/// An analog trace of a real signal scaled to the values in view, with the range labelled at the
+/// left of the view.
+fn real_shapes(
+    ui: &Ui,
+    rect: Rect,
+    view_range: &RangeInclusive<f32>,
+    final_time: u64,
+    values: &[(u64, f64)],
+    interpolation: Interpolation,
+) -> Vec<Shape> {
+    // scale to the values currently in view
+    let (min, max) = values
+        .iter()
+        .map(|(_, v)| *v)
+        .filter(|v| v.is_finite())
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| {
+            (lo.min(v), hi.max(v))
+        });
+    if min > max {
+        return vec![];
+    }
+    let (lo, hi) = if min == max {
+        (min - 1.0, max + 1.0)
+    } else {
+        (min, max)
+    };
+    let y_of = |v: f64| 0.1 + 0.8 * (v - lo) / (hi - lo);
+
+    // non-finite values (nan/inf) break the trace
+    let mut lines: Vec<Vec<PlotPoint>> = vec![vec![]];
+    let mut prev_y = None;
+    for &(t, v) in values {
+        let x = t as f64;
+        if !v.is_finite() {
+            if !lines.last().unwrap().is_empty() {
+                lines.push(vec![]);
             }
+            prev_y = None;
+            continue;
+        }
+        let y = y_of(v);
+        let line = lines.last_mut().unwrap();
+        if let (Interpolation::Step, Some(prev_y)) = (interpolation, prev_y) {
+            line.push(PlotPoint::new(x, prev_y));
+        }
+        line.push(PlotPoint::new(x, y));
+        prev_y = Some(y);
+    }
 
-            let stroke = ui.style().visuals.widgets.active.bg_stroke;
+    let stroke = ui.style().visuals.widgets.active.bg_stroke;
+    let mut shapes: Vec<Shape> = lines
+        .into_iter()
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            Shape::line(
+                line.iter()
+                    .map(|v| pos_from_val(*v, rect, final_time as usize))
+                    .collect(),
+                stroke,
+            )
+        })
+        .collect();
 
-            let mut shapes = vec![
-                Shape::line(
-                    pts_a
-                        .iter()
-                        .map(|v| pos_from_val(*v, rect, wave_data.final_time() as usize))
-                        .collect(),
-                    stroke,
-                ),
-                Shape::line(
-                    pts_b
-                        .iter()
-                        .map(|v| pos_from_val(*v, rect, wave_data.final_time() as usize))
-                        .collect(),
-                    stroke,
-                ),
-            ];
-            if scale > 0.05 {
-                let mut prev = &wave_data[first_ix];
-                // eprintln!("first_ix = {first_ix}, prev = {prev:?}");
-                let mut prev_start_x = first_ix as f32 + 0.5;
-                for (t, vs) in wave_data.range(first_ix + 1..last_ix) {
-                    let x = t as f32;
-                    let pos = pos_from_val(
-                        PlotPoint::new((prev_start_x + x) / 2.0, 0.5),
-                        rect,
-                        wave_data.final_time() as usize,
-                    );
-                    // TODO don't just use debug instance, have different format options
-                    let txt = format!("{prev:?}");
-                    let anchor = Align2::CENTER_CENTER;
-                    // let font = epaint::text::FontId::new(12.0, text::FontFamily::Monospace);
-                    // let sty = TextStyle::Monospace;
-                    let font = epaint::text::FontId::new(12.0, text::FontFamily::Monospace);
-                    let color = ui.style().visuals.text_color();
-                    // let fill_color = if true {
-                    //     egui::Color32::from_rgb(96, 119, 74)
-                    //     // Color32::from(Rgba::GREEN.multiply(0.2) + Rgba::from_white_alpha(0.08))
-                    // } else {
-                    //     Color32::from(Rgba::RED.multiply(0.3))
-                    // };
+    let font = epaint::text::FontId::new(10.0, text::FontFamily::Monospace);
+    let color = ui.style().visuals.weak_text_color();
+    let left = rect.left() + view_range.start() + 2.0;
+    for (v, y, anchor) in [
+        (hi, rect.top() + 1.0, Align2::LEFT_TOP),
+        (lo, rect.bottom() - 1.0, Align2::LEFT_BOTTOM),
+    ] {
+        let galley = ui.fonts(|f| f.layout_no_wrap(format!("{v:.4}"), font.clone(), color));
+        let rect = anchor.anchor_rect(Rect::from_min_size(pos2(left, y), galley.size()));
+        shapes.push(Shape::galley(rect.min, galley, color));
+    }
+    shapes
+}
 
-                    let galley = ui.fonts(|f| f.layout_no_wrap(txt, font, color));
-                    let rect = anchor.anchor_rect(Rect::from_min_size(pos, galley.size()));
-                    let fill_rect = rect.expand(2.0);
-                    if fill_rect.width() < (x - prev_start_x) * scale * 32.0 {
-                        // shapes.push(Shape::rect_filled(fill_rect, 2.0, fill_color));
-                        shapes.push(Shape::galley(rect.min, galley, color));
-                    }
-                    prev = vs;
-                    prev_start_x = x;
-                }
+/// The hexagon outlines of a bus with a label for each segment that's wide enough to fit one.
+///
+/// `changes` should be the changes covering `view` (see `vcd::Signal::range`), each value is held
+/// until the next change.
+fn bus_shapes<T>(
+    ui: &Ui,
+    rect: Rect,
+    scale: f32,
+    view: std::ops::Range<u64>,
+    final_time: u64,
+    changes: &[(u64, T)],
+    mut label: impl FnMut(&T) -> String,
+) -> Vec<Shape> {
+    let x_taper = 0.1;
+    let mut pts_a = vec![];
+    let mut pts_b = vec![];
+
+    let mut times = changes.iter().map(|(t, _)| *t);
+    let Some(t0) = times.next() else {
+        return vec![];
+    };
+    let mut x = t0 as f64;
+    // let mut y;
+    pts_a.push(PlotPoint::new(x, 0.5));
+    // pts_a.push(PlotPoint::new(x + 0.1, 0.1));
+    pts_b.push(PlotPoint::new(x, 0.5));
+    // pts_b.push(PlotPoint::new(x + 0.1, 0.9));
+    // let mut polarity = true;
+
+    for t in times {
+        pts_a.push(PlotPoint::new(x + x_taper, 0.1));
+        pts_b.push(PlotPoint::new(x + x_taper, 0.9));
+        x = t as f64;
+        pts_a.push(PlotPoint::new(x - x_taper, 0.1));
+        pts_a.push(PlotPoint::new(x, 0.5));
+        pts_b.push(PlotPoint::new(x - x_taper, 0.9));
+        pts_b.push(PlotPoint::new(x, 0.5));
+    }
+    let stroke = ui.style().visuals.widgets.active.bg_stroke;
+
+    let mut shapes = vec![
+        Shape::line(
+            pts_a
+                .iter()
+                .map(|v| pos_from_val(*v, rect, final_time as usize))
+                .collect(),
+            stroke,
+        ),
+        Shape::line(
+            pts_b
+                .iter()
+                .map(|v| pos_from_val(*v, rect, final_time as usize))
+                .collect(),
+            stroke,
+        ),
+    ];
+    if scale > 0.05 {
+        for w in changes.windows(2) {
+            let [(t0, value), (t1, _)] = w else {
+                unreachable!()
+            };
+            // keep the label in view when the segment is only partly visible
+            let start_x = (*t0 as f32).max(view.start as f32 + 0.5);
+            let end_x = (*t1 as f32).min(view.end as f32);
+            let pos = pos_from_val(
+                PlotPoint::new((start_x + end_x) as f64 / 2.0, 0.5),
+                rect,
+                final_time as usize,
+            );
+            let txt = label(value);
+            let anchor = Align2::CENTER_CENTER;
+            // let font = epaint::text::FontId::new(12.0, text::FontFamily::Monospace);
+            // let sty = TextStyle::Monospace;
+            let font = epaint::text::FontId::new(12.0, text::FontFamily::Monospace);
+            let color = ui.style().visuals.text_color();
+            // let fill_color = if true {
+            //     egui::Color32::from_rgb(96, 119, 74)
+            //     // Color32::from(Rgba::GREEN.multiply(0.2) + Rgba::from_white_alpha(0.08))
+            // } else {
+            //     Color32::from(Rgba::RED.multiply(0.3))
+            // };
+
+            let galley = ui.fonts(|f| f.layout_no_wrap(txt, font, color));
+            let rect = anchor.anchor_rect(Rect::from_min_size(pos, galley.size()));
+            let fill_rect = rect.expand(2.0);
+            if fill_rect.width() < (end_x - start_x) * scale * 32.0 {
+                // shapes.push(Shape::rect_filled(fill_rect, 2.0, fill_color));
+                shapes.push(Shape::galley(rect.min, galley, color));
             }
-            ui.painter().extend(shapes);
         }
     }
+    shapes
 }
@@ -0,0 +1,414 @@
+use crate::vcd::{Radix, ScopeType, ScopedVar, Signal, Value, Var, VarType, Waveform};
+use serde_json::Value as Json;
+use std::io::{self, Write};
+use std::ops::{Range, RangeInclusive};
+use std::sync::Arc;
+
+// WaveDrom's WaveJSON (https://wavedrom.com/tutorial.html), the timing diagrams used in our docs.
+// A lane's wave has a character for each cell: `0`/`1`/`x`/`z` levels, `p`/`n` clocks, `=` for
+// the next of its data labels and `.` to carry on with the previous cell.
+
+/// Exports are cut short after this many cells, any more won't fit in a diagram.
+const MAX_CELLS: u64 = 1000;
+
+fn invalid(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+/// The period of a one bit signal that toggles evenly over `range`, along with the time of one of
+/// its rising edges.
+pub fn clock_period(signal: &Signal, range: Range<u64>) -> Option<(u64, u64)> {
+    if signal.is_real() || signal.is_string() || signal.width() != 1 {
+        return None;
+    }
+    let times = signal.change_times(range);
+    if times.len() < 3 {
+        return None;
+    }
+    let half = times[1] - times[0];
+    if half == 0 || times.windows(2).any(|w| w[1] - w[0] != half) {
+        return None;
+    }
+    let values: Vec<_> = times.iter().map(|&t| signal.bit_at(t)).collect();
+    let toggles = values.windows(2).all(|w| {
+        matches!(
+            (w[0], w[1]),
+            (Some(Value::V0), Some(Value::V1)) | (Some(Value::V1), Some(Value::V0))
+        )
+    });
+    if !toggles {
+        return None;
+    }
+    let rising = if values[0] == Some(Value::V1) {
+        times[0]
+    } else {
+        times[1]
+    };
+    Some((2 * half, rising))
+}
+
+/// The wave character for the value of `signal` at `time` and the data label for `=`.
+fn sample(signal: &Signal, time: u64) -> (char, Option<String>) {
+    if signal.is_real() {
+        match signal.real_at(time) {
+            Some(v) => ('=', Some(v.to_string())),
+            None => ('x', None),
+        }
+    } else if signal.is_string() {
+        match signal.string_at(time) {
+            Some(s) => ('=', Some(s.to_owned())),
+            None => ('x', None),
+        }
+    } else if signal.width() == 1 {
+        (signal.bit_at(time).unwrap_or(Value::X).to_char(), None)
+    } else {
+        match signal.value_at(time) {
+            None => ('x', None),
+            Some(bits) if bits.iter().all(|&v| v == Value::X) => ('x', None),
+            Some(bits) if bits.iter().all(|&v| v == Value::Z) => ('z', None),
+            Some(bits) => {
+                let label = signal
+                    .label(&bits)
+                    .map_or_else(|| Radix::Hex.format(&bits), str::to_owned);
+                ('=', Some(label))
+            }
+        }
+    }
+}
+
+/// Write one lane of WaveJSON, `cells` cells of width `cell` starting at `origin`.
+fn write_lane(
+    w: &mut impl Write,
+    name: &str,
+    signal: &Signal,
+    clock: Option<(u64, u64)>,
+    origin: u64,
+    cell: u64,
+    cells: u64,
+) -> io::Result<()> {
+    let mut wave = String::new();
+    let mut data = vec![];
+    let mut period = 1;
+    // clocks that line up with the cells get drawn as clocks
+    let phase =
+        clock.map(|(p, rising)| (p, (rising as i128 - origin as i128).rem_euclid(p as i128)));
+    match phase {
+        Some((p, phase)) if p % cell == 0 && (phase == 0 || phase * 2 == p as i128) => {
+            period = p / cell;
+            wave.push(if phase == 0 { 'p' } else { 'n' });
+            for _ in 1..cells.div_ceil(period) {
+                wave.push('.');
+            }
+        }
+        _ => {
+            let mut last = None;
+            for i in 0..cells {
+                let (c, label) = sample(signal, origin + i * cell);
+                if last.as_ref() == Some(&(c, label.clone())) {
+                    wave.push('.');
+                    continue;
+                }
+                wave.push(c);
+                data.extend(label.clone());
+                last = Some((c, label));
+            }
+        }
+    }
+
+    let json = |s: &str| serde_json::to_string(s).unwrap();
+    write!(w, "{{\"name\": {}, \"wave\": {}", json(name), json(&wave))?;
+    if !data.is_empty() {
+        let data: Vec<_> = data.iter().map(|d| json(d)).collect();
+        write!(w, ", \"data\": [{}]", data.join(", "))?;
+    }
+    if period != 1 {
+        write!(w, ", \"period\": {period}")?;
+    }
+    write!(w, "}}")
+}
+
+/// Write `lanes` over the times in `range` as WaveJSON.
+///
+/// When one of the signals is a clock each cell is one of its periods, starting from its first
+/// rising edge in the range. Otherwise the cells are as wide as they can be with every change
+/// still landing on a cell boundary. Buses are labelled with their value in hex (or their label).
+pub fn write_wavejson(
+    w: &mut impl Write,
+    lanes: &[(&str, &Signal)],
+    range: RangeInclusive<u64>,
+) -> io::Result<()> {
+    let (start, end) = (*range.start(), *range.end());
+    let times = start..end.saturating_add(1);
+    let clocks: Vec<_> = lanes
+        .iter()
+        .map(|(_, signal)| clock_period(signal, times.clone()))
+        .collect();
+    let (cell, origin) = match clocks.iter().flatten().min_by_key(|(period, _)| *period) {
+        Some(&(period, rising)) => (period, rising - (rising - start) / period * period),
+        None => {
+            let mut cell = end - start;
+            for (_, signal) in lanes {
+                for &t in signal.change_times(times.clone()) {
+                    cell = gcd(cell, t - start);
+                }
+            }
+            // an odd change can make too many cells
+            (cell.max((end - start).div_ceil(MAX_CELLS)).max(1), start)
+        }
+    };
+    let cells = end
+        .saturating_sub(origin)
+        .div_ceil(cell)
+        .clamp(1, MAX_CELLS);
+
+    writeln!(w, "{{\"signal\": [")?;
+    for (i, ((name, signal), clock)) in lanes.iter().zip(clocks).enumerate() {
+        write!(w, "  ")?;
+        write_lane(w, name, signal, clock, origin, cell, cells)?;
+        writeln!(w, "{}", if i + 1 < lanes.len() { "," } else { "" })?;
+    }
+    writeln!(w, "]}}")
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// Make a signal from a lane of WaveJSON. Lanes with data labels become string signals.
+fn read_lane(lane: &serde_json::Map<String, Json>, start: u64, tick: u64) -> (bool, Signal) {
+    let wave = lane.get("wave").and_then(Json::as_str).unwrap_or_default();
+    let period = lane
+        .get("period")
+        .and_then(Json::as_f64)
+        .filter(|&p| p > 0.0)
+        .unwrap_or(1.0);
+    let phase = lane.get("phase").and_then(Json::as_f64).unwrap_or(0.0);
+    // the labels can be a list or one string split by spaces
+    let data: Vec<String> = match lane.get("data") {
+        Some(Json::Array(data)) => data
+            .iter()
+            .map(|d| d.as_str().map_or_else(|| d.to_string(), str::to_owned))
+            .collect(),
+        Some(Json::String(data)) => data.split_whitespace().map(str::to_owned).collect(),
+        _ => vec![],
+    };
+    let mut data = data.into_iter();
+
+    let is_data = wave.chars().any(|c| c == '=' || ('2'..='9').contains(&c));
+    let mut signal = if is_data {
+        Signal::new_string()
+    } else {
+        Signal::new(1)
+    };
+    // the time at a number of cells in, phase moves the diagram left which can go before the start
+    let time = |cells: f64| {
+        (start as f64 + (cells - phase) * period * tick as f64)
+            .round()
+            .max(0.0) as u64
+    };
+    let insert = |signal: &mut Signal, cells: f64, value: Value| {
+        if is_data {
+            signal.insert_string(time(cells), &value.to_char().to_string());
+        } else {
+            signal.insert(time(cells), vec![value]);
+        }
+    };
+
+    let mut last = None;
+    for (i, c) in wave.chars().enumerate() {
+        let i = i as f64;
+        let c = match c {
+            // only clocks need anything doing to carry on
+            '.' | '|' => match last {
+                Some(c @ ('p' | 'P' | 'n' | 'N')) => c,
+                _ => continue,
+            },
+            c => c,
+        };
+        last = Some(c);
+        match c {
+            'p' | 'P' => {
+                insert(&mut signal, i, Value::V1);
+                insert(&mut signal, i + 0.5, Value::V0);
+            }
+            'n' | 'N' => {
+                insert(&mut signal, i, Value::V0);
+                insert(&mut signal, i + 0.5, Value::V1);
+            }
+            '0' | 'l' | 'L' | 'd' => insert(&mut signal, i, Value::V0),
+            '1' | 'h' | 'H' | 'u' => insert(&mut signal, i, Value::V1),
+            'x' => insert(&mut signal, i, Value::X),
+            'z' => insert(&mut signal, i, Value::Z),
+            '=' | '2'..='9' => signal.insert_string(time(i), &data.next().unwrap_or_default()),
+            _ => (),
+        }
+    }
+    signal.extend_to(time(wave.chars().count() as f64));
+    (is_data, signal)
+}
+
+/// Add the lanes in `items` to `vars`, groups become scopes.
+fn read_lanes(
+    items: &[Json],
+    scopes: &mut Vec<(ScopeType, String)>,
+    vars: &mut Vec<(ScopedVar, Arc<Signal>)>,
+    start: u64,
+    tick: u64,
+) {
+    for item in items {
+        match item {
+            // a group is a list of lanes that can start with a name
+            Json::Array(group) => match group.split_first() {
+                Some((Json::String(name), lanes)) => {
+                    scopes.push((ScopeType::Module, name.clone()));
+                    read_lanes(lanes, scopes, vars, start, tick);
+                    scopes.pop();
+                }
+                _ => read_lanes(group, scopes, vars, start, tick),
+            },
+            // lanes without a wave are spacers
+            Json::Object(lane) if lane.contains_key("wave") => {
+                let (is_data, signal) = read_lane(lane, start, tick);
+                let var = Var {
+                    var_type: if is_data {
+                        VarType::String
+                    } else {
+                        VarType::Wire
+                    },
+                    size: 1,
+                    code: ::vcd::IdCode::from(vars.len() as u64),
+                    reference: lane
+                        .get("name")
+                        .and_then(Json::as_str)
+                        .unwrap_or_default()
+                        .to_owned(),
+                    index: None,
+                };
+                let var = ScopedVar {
+                    scopes: scopes.clone(),
+                    var,
+                };
+                vars.push((var, Arc::new(signal)));
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Read a WaveJSON diagram, each cell taking `tick` ticks from `start`. Groups become scopes and
+/// lanes with data labels become string signals. The text can be the JavaScript object the
+/// WaveDrom editor takes, with unquoted keys and trailing commas, not just strict JSON.
+pub fn read_wavejson(text: &str, start: u64, tick: u64) -> io::Result<Waveform> {
+    let json: Json = json5::from_str(text).map_err(|err| invalid(err.to_string()))?;
+    let lanes = json
+        .get("signal")
+        .and_then(Json::as_array)
+        .ok_or_else(|| invalid("WaveJSON needs a \"signal\" list"))?;
+    let mut vars = vec![];
+    read_lanes(lanes, &mut vec![], &mut vars, start, tick);
+    let final_time = vars
+        .iter()
+        .map(|(_, signal)| signal.final_time())
+        .max()
+        .unwrap_or(start);
+    Ok(Waveform {
+        vars,
+        final_time,
+        timescale: None,
+        not_recorded: vec![],
+        warnings: vec![],
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vcd::read_clocked_vcd;
+
+    #[test]
+    fn test_wavejson() {
+        let vcd = "$scope module top $end
+$var wire 1 ! clk $end
+$var wire 8 \" data $end
+$var wire 1 # valid $end
+$upscope $end
+$enddefinitions $end
+#0
+0!
+bxxxxxxxx \"
+0#
+#5
+1!
+#10
+0!
+#15
+1!
+b10100101 \"
+1#
+#20
+0!
+#25
+1!
+b00001100 \"
+#30
+0!
+#35
+1!
+0#
+#40
+0!
+";
+        let wave = read_clocked_vcd(&mut vcd.as_bytes()).unwrap();
+        let lanes: Vec<_> = wave
+            .vars
+            .iter()
+            .map(|(v, s)| (v.var.reference.as_str(), &**s))
+            .collect();
+        let mut out = vec![];
+        write_wavejson(&mut out, &lanes, 5..=40).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(
+            out,
+            r#"{"signal": [
+  {"name": "clk", "wave": "p..."},
+  {"name": "data", "wave": "x==.", "data": ["a5", "c"]},
+  {"name": "valid", "wave": "01.0"}
+]}
+"#
+        );
+
+        // reading it back with the clock period gives the same edges
+        let read = read_wavejson(&out, 5, 10).unwrap();
+        assert_eq!(read.final_time, 45);
+        let clk = &read.vars[0].1;
+        assert_eq!(clocked_changes(clk), [5, 10, 15, 20, 25, 30, 35, 40]);
+        let data = &read.vars[1].1;
+        assert!(data.is_string());
+        assert_eq!(data.string_at(27), Some("c"));
+        let valid = &read.vars[2].1;
+        assert_eq!(valid.bit_at(14), Some(Value::V0));
+        assert_eq!(valid.bit_at(15), Some(Value::V1));
+        assert_eq!(valid.bit_at(35), Some(Value::V0));
+
+        let editor = "{signal: [
+  {name: 'clk', wave: 'n.', period: 2},
+  ['bus', {name: 'a', wave: 'x=.=', data: 'one two'}],
+  {},
+]}";
+        let read = read_wavejson(editor, 0, 4).unwrap();
+        assert_eq!(read.vars.len(), 2);
+        assert_eq!(clocked_changes(&read.vars[0].1), [0, 4, 8, 12]);
+        let (a, a_sig) = &read.vars[1];
+        assert_eq!(a.scopes, [(ScopeType::Module, "bus".to_owned())]);
+        assert_eq!(a_sig.string_at(4), Some("one"));
+        assert_eq!(a_sig.string_at(12), Some("two"));
+    }
+
+    fn clocked_changes(signal: &Signal) -> Vec<u64> {
+        signal.change_times(0..u64::MAX).to_vec()
+    }
+}